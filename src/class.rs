@@ -0,0 +1,77 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::function::Function;
+use crate::value::Value;
+
+/// A class, with its methods and optional superclass.
+pub struct Class<'src> {
+    pub name: String,
+    pub superclass: Option<Rc<Class<'src>>>,
+    pub methods: HashMap<String, Rc<Function<'src>>>,
+}
+
+impl<'src> Class<'src> {
+    /// Find the method `name` on this class or its nearest ancestor.
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function<'src>>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+
+    /// The number of arguments the initializer takes.
+    pub fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+}
+
+/// Don't walk the methods, their closures may contain this class.
+impl fmt::Debug for Class<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Class")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An instance of a class.
+pub struct Instance<'src> {
+    pub class: Rc<Class<'src>>,
+    fields: HashMap<String, Value<'src>>,
+}
+
+impl<'src> Instance<'src> {
+    pub fn new(class: Rc<Class<'src>>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Look up the property `name` on `instance`. Fields shadow methods, and
+    /// methods are bound to the instance.
+    pub fn get(instance: &Rc<RefCell<Instance<'src>>>, name: &str) -> Option<Value<'src>> {
+        if let Some(value) = instance.borrow().fields.get(name) {
+            return Some(value.clone());
+        }
+        let method = instance.borrow().class.find_method(name)?;
+        let bound = method.bind(Value::Instance(Rc::clone(instance)));
+        Some(Value::Function(Rc::new(bound)))
+    }
+
+    pub fn set(&mut self, name: String, value: Value<'src>) {
+        self.fields.insert(name, value);
+    }
+}
+
+/// Don't walk the fields, they may contain this instance.
+impl fmt::Debug for Instance<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instance")
+            .field("class", &self.class.name)
+            .finish_non_exhaustive()
+    }
+}
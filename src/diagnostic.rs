@@ -0,0 +1,594 @@
+use std::fmt::Write;
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::source::{FileId, Source, SourceMap, Span};
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// A span of source called out by a [`Diagnostic`], with a message about it
+/// unless the diagnostic's own message says enough.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: Option<String>,
+    /// Whether this is where the problem is, rather than related context.
+    pub primary: bool,
+}
+
+impl Label {
+    pub fn primary(span: Span) -> Self {
+        Self {
+            span,
+            message: None,
+            primary: true,
+        }
+    }
+
+    pub fn secondary(span: Span) -> Self {
+        Self {
+            span,
+            message: None,
+            primary: false,
+        }
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
+
+/// A problem found in the source, ready to be rendered for a person or tool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// A stable code identifying the kind of problem, like `E0001`.
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.labels.push(label);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Returns the span of the first primary label, if any.
+    pub fn primary_span(&self) -> Option<&Span> {
+        self.labels
+            .iter()
+            .find(|label| label.primary)
+            .map(|label| &label.span)
+    }
+}
+
+/// Tabs are expanded to this many spaces, so carets line up under them.
+const TAB_WIDTH: usize = 4;
+
+/// The most lines shown inside a multi-line label before eliding the rest.
+const MAX_INNER_LINES: usize = 3;
+
+/// Terminal styles, used only when rendering in colour.
+#[derive(Debug, Clone, Copy)]
+enum Style {
+    Error,
+    Warning,
+    Secondary,
+    Gutter,
+    Bold,
+}
+
+impl Style {
+    fn ansi(self) -> &'static str {
+        match self {
+            Style::Error => "\x1b[1;31m",
+            Style::Warning => "\x1b[1;33m",
+            Style::Secondary | Style::Gutter => "\x1b[1;34m",
+            Style::Bold => "\x1b[1m",
+        }
+    }
+}
+
+/// A label's marker on one line, in display columns.
+#[derive(Debug)]
+struct Marker<'a> {
+    start: usize,
+    end: usize,
+    primary: bool,
+    /// Shown on the last line of the label only.
+    message: Option<&'a str>,
+}
+
+/// Renders [`Diagnostic`]s as text, in the style of rustc:
+///
+/// ```text
+/// error[E0004]: unclosed parenthesis
+///  --> script.lox:2:9
+///   |
+/// 2 | var b = (a + 2;
+///   |         ^     - expected ')' here
+///   |         |
+///   |         this '(' is never closed
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Renderer {
+    color: bool,
+}
+
+impl Renderer {
+    /// A renderer that writes plain text.
+    pub fn plain() -> Self {
+        Self { color: false }
+    }
+
+    /// A renderer that colours its output with ANSI escapes, for terminals.
+    pub fn colored() -> Self {
+        Self { color: true }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+        let mut out = String::new();
+        let severity_style = match diagnostic.severity {
+            Severity::Error => Style::Error,
+            Severity::Warning => Style::Warning,
+        };
+        let heading = match diagnostic.code {
+            Some(code) => format!("{}[{code}]", diagnostic.severity.as_str()),
+            None => diagnostic.severity.as_str().to_owned(),
+        };
+        let message = self.paint(Style::Bold, &format!(": {}", diagnostic.message));
+        writeln!(out, "{}{message}", self.paint(severity_style, &heading)).unwrap();
+
+        // Labels grouped by file, starting with the primary label's file.
+        let mut files: Vec<FileId> = Vec::new();
+        let primary_file = diagnostic.primary_span().map(|span| span.file);
+        for file in primary_file
+            .into_iter()
+            .chain(diagnostic.labels.iter().map(|label| label.span.file))
+        {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+        let sources: Vec<(&Source, Vec<&Label>)> = files
+            .into_iter()
+            .filter_map(|file| {
+                let labels = diagnostic
+                    .labels
+                    .iter()
+                    .filter(|label| label.span.file == file)
+                    .collect();
+                Some((sources.get(file)?, labels))
+            })
+            .collect();
+
+        let max_line = sources
+            .iter()
+            .flat_map(|(source, labels)| {
+                labels
+                    .iter()
+                    .map(|label| source.line_col(label.span.end as usize).line + 1)
+            })
+            .max()
+            .unwrap_or(1);
+        let width = max_line.to_string().len();
+
+        for (i, (source, labels)) in sources.iter().enumerate() {
+            self.render_snippet(&mut out, source, labels, width, i == 0);
+        }
+
+        let gutter = " ".repeat(width);
+        for note in &diagnostic.notes {
+            let equals = self.paint(Style::Gutter, "=");
+            writeln!(
+                out,
+                "{gutter} {equals} {}: {note}",
+                self.paint(Style::Bold, "note")
+            )
+            .unwrap();
+        }
+        if let Some(help) = &diagnostic.help {
+            let equals = self.paint(Style::Gutter, "=");
+            writeln!(
+                out,
+                "{gutter} {equals} {}: {help}",
+                self.paint(Style::Bold, "help")
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    /// Render the lines of `source` that `labels` point at, with a gutter of
+    /// line numbers `width` wide.
+    fn render_snippet<'l>(
+        &self,
+        out: &mut String,
+        source: &Source,
+        labels: &[&'l Label],
+        width: usize,
+        first: bool,
+    ) {
+        let gutter = " ".repeat(width);
+        let bar = self.paint(Style::Gutter, "|");
+
+        // Locate from the primary label, or the first one.
+        let anchor = labels
+            .iter()
+            .find(|label| label.primary)
+            .unwrap_or(&labels[0]);
+        let position = source.line_col(anchor.span.start as usize);
+        let arrow = self.paint(Style::Gutter, if first { "-->" } else { ":::" });
+        writeln!(
+            out,
+            "{gutter}{arrow} {}:{}:{}",
+            source.path(),
+            position.line + 1,
+            position.char + 1
+        )
+        .unwrap();
+        writeln!(out, "{gutter} {bar}").unwrap();
+
+        // Each label's markers, by line.
+        let mut lines: Vec<(usize, Vec<Marker<'l>>)> = Vec::new();
+        let mut add = |line: usize, marker: Option<Marker<'l>>| {
+            let index = match lines.iter().position(|(l, _)| *l == line) {
+                Some(index) => index,
+                None => {
+                    lines.push((line, Vec::new()));
+                    lines.len() - 1
+                }
+            };
+            if let Some(marker) = marker {
+                lines[index].1.push(marker);
+            }
+        };
+        for label in labels {
+            let start = source.line_col(label.span.start as usize);
+            let end = source.line_col(label.span.end as usize);
+            let (start_line, end_line) = (start.line as usize, end.line as usize);
+            let start_text = source.line_text(start_line).unwrap_or_default();
+            let end_text = source.line_text(end_line).unwrap_or_default();
+            let start_col = display_column(start_text, start.byte as usize);
+            let end_col = display_column(end_text, end.byte as usize);
+            let message = label.message.as_deref();
+
+            if start_line == end_line {
+                add(
+                    start_line,
+                    Some(Marker {
+                        start: start_col,
+                        end: end_col,
+                        primary: label.primary,
+                        message,
+                    }),
+                );
+                continue;
+            }
+
+            // A multi-line label marks from its start to the end of the first
+            // line, shows some lines between, and marks up to its end on the
+            // last line.
+            let first_line_end = display_column(start_text, start_text.len());
+            add(
+                start_line,
+                Some(Marker {
+                    start: start_col,
+                    end: first_line_end,
+                    primary: label.primary,
+                    message: None,
+                }),
+            );
+            let inner = (start_line + 1)..end_line;
+            if inner.len() <= MAX_INNER_LINES {
+                for line in inner {
+                    add(line, None);
+                }
+            } else {
+                add(start_line + 1, None);
+                add(end_line - 1, None);
+            }
+            add(
+                end_line,
+                Some(Marker {
+                    start: 0,
+                    end: end_col,
+                    primary: label.primary,
+                    message,
+                }),
+            );
+        }
+        lines.sort_by_key(|(line, _)| *line);
+
+        let mut previous: Option<usize> = None;
+        for (line, mut markers) in lines {
+            if previous.is_some_and(|previous| line > previous + 1) {
+                writeln!(out, "{}", self.paint(Style::Gutter, "...")).unwrap();
+            }
+            previous = Some(line);
+
+            let text = expand_tabs(source.line_text(line).unwrap_or_default());
+            let number = self.paint(Style::Gutter, &format!("{:>width$}", line + 1));
+            writeln!(out, "{}", format!("{number} {bar} {text}").trim_end()).unwrap();
+
+            markers.sort_by_key(|marker| marker.start);
+            for row in self.marker_rows(&markers) {
+                writeln!(out, "{}", format!("{gutter} {bar} {row}").trim_end()).unwrap();
+            }
+        }
+    }
+
+    /// Render the underline for `markers` on a line, sorted by start column.
+    /// The last marker's message follows its underline, and the others hang
+    /// below it, joined to their markers by `|`.
+    fn marker_rows(&self, markers: &[Marker]) -> Vec<String> {
+        if markers.is_empty() {
+            return Vec::new();
+        }
+
+        let mut underline = String::new();
+        let mut column = 0;
+        for marker in markers {
+            let (c, style) = if marker.primary {
+                ('^', Style::Error)
+            } else {
+                ('-', Style::Secondary)
+            };
+            let start = marker.start.max(column);
+            let end = marker.end.max(marker.start + 1);
+            if end <= start {
+                continue;
+            }
+            underline.push_str(&" ".repeat(start - column));
+            let run: String = std::iter::repeat_n(c, end - start).collect();
+            underline.push_str(&self.paint(style, &run));
+            column = end;
+        }
+        let (last, rest) = markers.split_last().unwrap();
+        if let Some(message) = last.message {
+            underline.push(' ');
+            underline.push_str(&self.paint(marker_style(last), message));
+        }
+
+        let mut rows = vec![underline];
+        let hanging: Vec<&Marker> = rest.iter().filter(|m| m.message.is_some()).collect();
+        for (i, marker) in hanging.iter().enumerate().rev() {
+            // Connectors for this and every hanging marker left of it, then
+            // the message in place of this one's connector.
+            let connectors = |row: &mut String, upto: usize| {
+                let mut column = 0;
+                for marker in &hanging[..upto] {
+                    row.push_str(&" ".repeat(marker.start.saturating_sub(column)));
+                    row.push_str(&self.paint(marker_style(marker), "|"));
+                    column = marker.start + 1;
+                }
+                column
+            };
+            let mut row = String::new();
+            connectors(&mut row, i + 1);
+            rows.push(row);
+
+            let mut row = String::new();
+            let column = connectors(&mut row, i);
+            row.push_str(&" ".repeat(marker.start.saturating_sub(column)));
+            row.push_str(&self.paint(marker_style(marker), marker.message.unwrap()));
+            rows.push(row);
+        }
+        rows
+    }
+
+    fn paint(&self, style: Style, text: &str) -> String {
+        if self.color {
+            format!("{}{text}\x1b[0m", style.ansi())
+        } else {
+            text.to_owned()
+        }
+    }
+}
+
+fn marker_style(marker: &Marker) -> Style {
+    if marker.primary {
+        Style::Error
+    } else {
+        Style::Secondary
+    }
+}
+
+/// Returns the display column of byte `offset` in `line`, counting tabs as
+/// [`TAB_WIDTH`] columns and wide chars, like CJK, as two. Offsets past the
+/// end of the line, like its newline, are one column each.
+fn display_column(line: &str, offset: usize) -> usize {
+    let prefix = line.get(..offset).unwrap_or(line);
+    let width: usize = prefix.chars().map(char_width).sum();
+    width + offset.saturating_sub(line.len())
+}
+
+fn char_width(c: char) -> usize {
+    if c == '\t' {
+        TAB_WIDTH
+    } else {
+        c.width().unwrap_or(0)
+    }
+}
+
+fn expand_tabs(line: &str) -> String {
+    line.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn span(start: u32, end: u32) -> Span {
+        Span {
+            file: FileId(0),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn labels_notes_and_help() {
+        let mut sources = SourceMap::new();
+        sources.add("script.lox", "var a = 1;\nvar b = (a + 2;\n");
+        let diagnostic = Diagnostic::error("unclosed parenthesis")
+            .with_code("E0001")
+            .with_label(Label::primary(span(19, 20)).with_message("this '(' is never closed"))
+            .with_label(Label::secondary(span(25, 26)).with_message("expected ')' here"))
+            .with_note("parentheses must be balanced")
+            .with_help("add a ')'");
+        let actual = Renderer::plain().render(&diagnostic, &sources);
+        let expected = indoc! {"
+            error[E0001]: unclosed parenthesis
+             --> script.lox:2:9
+              |
+            2 | var b = (a + 2;
+              |         ^     - expected ')' here
+              |         |
+              |         this '(' is never closed
+              = note: parentheses must be balanced
+              = help: add a ')'
+        "};
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn multi_line_span() {
+        let mut sources = SourceMap::new();
+        let text = "fun f() {\n  a;\n  b;\n  c;\n  d;\n}\nf();\n";
+        sources.add("script.lox", text);
+        let diagnostic = Diagnostic::warning("function body")
+            .with_label(Label::primary(span(8, 31)).with_message("ends here"))
+            .with_label(Label::secondary(span(32, 35)).with_message("called here"));
+        let actual = Renderer::plain().render(&diagnostic, &sources);
+        let expected = indoc! {"
+            warning: function body
+             --> script.lox:1:9
+              |
+            1 | fun f() {
+              |         ^
+            2 |   a;
+            ...
+            5 |   d;
+            6 | }
+              | ^ ends here
+            7 | f();
+              | --- called here
+        "};
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn tabs_and_wide_chars() {
+        let mut sources = SourceMap::new();
+        sources.add("script.lox", "\tprint \"変数\" - x;");
+        let diagnostic = Diagnostic::error("operands must be numbers")
+            .with_label(Label::primary(span(16, 17)))
+            .with_label(Label::secondary(span(7, 15)).with_message("a string"));
+        let actual = Renderer::plain().render(&diagnostic, &sources);
+        let expected = indoc! {"
+            error: operands must be numbers
+             --> script.lox:1:13
+              |
+            1 |     print \"変数\" - x;
+              |           ------ ^
+              |           |
+              |           a string
+        "};
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn labels_in_several_files() {
+        let mut sources = SourceMap::new();
+        sources.add("<repl:1>", "fun f(x) { return -x; }");
+        sources.add("<repl:2>", "f(\"a\");");
+        let diagnostic = Diagnostic::error("operand must be a number")
+            .with_label(Label::primary(span(18, 19)))
+            .with_label(
+                Label::secondary(Span {
+                    file: FileId(1),
+                    start: 0,
+                    end: 6,
+                })
+                .with_message("called here"),
+            );
+        let actual = Renderer::plain().render(&diagnostic, &sources);
+        let expected = indoc! {"
+            error: operand must be a number
+             --> <repl:1>:1:19
+              |
+            1 | fun f(x) { return -x; }
+              |                   ^
+             ::: <repl:2>:1:1
+              |
+            1 | f(\"a\");
+              | ------ called here
+        "};
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn colored() {
+        let mut sources = SourceMap::new();
+        sources.add("script.lox", "x;");
+        let diagnostic = Diagnostic::error("oops").with_label(Label::primary(span(0, 1)));
+        let actual = Renderer::colored().render(&diagnostic, &sources);
+        assert!(actual.starts_with("\x1b[1;31merror\x1b[0m\x1b[1m: oops\x1b[0m\n"));
+        assert!(actual.contains("\x1b[1;31m^\x1b[0m"));
+    }
+}
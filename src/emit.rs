@@ -0,0 +1,417 @@
+use camino::Utf8Path;
+use serde::Serialize;
+
+use crate::diagnostic::{Diagnostic, Label};
+use crate::source::{LineCol, SourceMap, Span};
+
+/// A [`Diagnostic`] as a JSON record, one per line of output:
+///
+/// ```json
+/// {"severity":"error","code":"E0004","message":"unclosed parenthesis",
+///  "file":"script.lox","span":{"start":19,"end":20},
+///  "range":{"start":{"line":2,"column":9},"end":{"line":2,"column":10}},
+///  "labels":[{"file":"script.lox","span":…,"range":…,
+///             "message":"this '(' is never closed","primary":true}],
+///  "notes":[],"help":null}
+/// ```
+///
+/// The top-level location is the primary label's, and a label's message is
+/// left out if it has none. Spans are byte offsets, and lines and columns are
+/// 1-based, with columns counted in chars. Fields may be added, but not
+/// removed or changed.
+#[derive(Debug, Serialize)]
+struct JsonDiagnostic<'a> {
+    severity: &'static str,
+    code: Option<&'static str>,
+    message: &'a str,
+    #[serde(flatten)]
+    location: Option<JsonLocation<'a>>,
+    labels: Vec<JsonLabel<'a>>,
+    notes: &'a [String],
+    help: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonLabel<'a> {
+    #[serde(flatten)]
+    location: JsonLocation<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+    primary: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonLocation<'a> {
+    file: &'a str,
+    span: JsonSpan,
+    range: JsonRange,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonSpan {
+    start: u32,
+    end: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonRange {
+    start: JsonPosition,
+    end: JsonPosition,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonPosition {
+    line: u32,
+    column: u32,
+}
+
+/// Locate `span`, or return `None` if its file isn't in `sources`.
+fn json_location<'a>(span: &Span, sources: &'a SourceMap) -> Option<JsonLocation<'a>> {
+    let source = sources.get(span.file)?;
+    let range = source.span_to_range(span);
+    let position = |line_col: LineCol| JsonPosition {
+        line: line_col.line + 1,
+        column: line_col.char + 1,
+    };
+    Some(JsonLocation {
+        file: source.path().as_str(),
+        span: JsonSpan {
+            start: span.start,
+            end: span.end,
+        },
+        range: JsonRange {
+            start: position(range.start),
+            end: position(range.end),
+        },
+    })
+}
+
+/// Format `diagnostic` as a single line of JSON. See [`JsonDiagnostic`] for
+/// the schema.
+pub fn json(diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+    let labels = diagnostic
+        .labels
+        .iter()
+        .filter_map(|label| {
+            Some(JsonLabel {
+                location: json_location(&label.span, sources)?,
+                message: label.message.as_deref(),
+                primary: label.primary,
+            })
+        })
+        .collect();
+    let record = JsonDiagnostic {
+        severity: diagnostic.severity.as_str(),
+        code: diagnostic.code,
+        message: &diagnostic.message,
+        location: diagnostic
+            .primary_span()
+            .and_then(|span| json_location(span, sources)),
+        labels,
+        notes: &diagnostic.notes,
+        help: diagnostic.help.as_deref(),
+    };
+    serde_json::to_string(&record).expect("diagnostics serialize to JSON")
+}
+
+/// The SARIF 2.1.0 version and schema, for code scanning tools.
+const SARIF_VERSION: &str = "2.1.0";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Debug, Serialize)]
+struct SarifLog {
+    version: &'static str,
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    runs: [SarifRun; 1],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRun {
+    tool: SarifTool,
+    /// SARIF's default, as in the Language Server Protocol.
+    column_kind: &'static str,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifDriver {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rule_id: Option<&'static str>,
+    level: &'static str,
+    message: SarifMessage,
+    locations: Vec<SarifLocation>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related_locations: Vec<SarifLocation>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    physical_location: SarifPhysicalLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<SarifMessage>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    region: SarifRegion,
+}
+
+/// A URI reference to the file: relative paths stay relative, and absolute
+/// ones are `file:` URIs.
+#[derive(Debug, Serialize)]
+struct SarifArtifactLocation {
+    uri: String,
+}
+
+/// Lines and columns are 1-based, with columns in UTF-16 code units.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: u32,
+    start_column: u32,
+    end_line: u32,
+    end_column: u32,
+    byte_offset: u32,
+    byte_length: u32,
+}
+
+fn sarif_location(label: &Label, sources: &SourceMap) -> Option<SarifLocation> {
+    let source = sources.get(label.span.file)?;
+    let range = source.span_to_range(&label.span);
+    Some(SarifLocation {
+        physical_location: SarifPhysicalLocation {
+            artifact_location: SarifArtifactLocation {
+                uri: artifact_uri(source.path()),
+            },
+            region: SarifRegion {
+                start_line: range.start.line + 1,
+                start_column: range.start.utf16 + 1,
+                end_line: range.end.line + 1,
+                end_column: range.end.utf16 + 1,
+                byte_offset: label.span.start,
+                byte_length: label.span.end - label.span.start,
+            },
+        },
+        message: label.message.clone().map(|text| SarifMessage { text }),
+    })
+}
+
+/// Returns `path` as a URI reference, percent-encoding the bytes that
+/// aren't allowed in one, like spaces, `%` and `#`.
+fn artifact_uri(path: &Utf8Path) -> String {
+    let mut uri = String::new();
+    if path.is_absolute() {
+        uri.push_str("file://");
+        // Like `C:\dir` on Windows.
+        if !path.as_str().starts_with('/') {
+            uri.push('/');
+        }
+    }
+    for byte in path.as_str().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            b'\\' if cfg!(windows) => uri.push('/'),
+            // A colon in the first segment of a relative reference would
+            // make it look like a scheme.
+            b':' if path.is_absolute() => uri.push(':'),
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+/// Format `diagnostics` as a SARIF 2.1.0 log with a single run.
+pub fn sarif(diagnostics: &[Diagnostic], sources: &SourceMap) -> String {
+    let results = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let (primary, secondary): (Vec<&Label>, Vec<&Label>) =
+                diagnostic.labels.iter().partition(|label| label.primary);
+            let mut text = diagnostic.message.clone();
+            for note in &diagnostic.notes {
+                text = format!("{text}\nnote: {note}");
+            }
+            if let Some(help) = &diagnostic.help {
+                text = format!("{text}\nhelp: {help}");
+            }
+            SarifResult {
+                rule_id: diagnostic.code,
+                level: diagnostic.severity.as_str(),
+                message: SarifMessage { text },
+                locations: primary
+                    .into_iter()
+                    .filter_map(|label| sarif_location(label, sources))
+                    .collect(),
+                related_locations: secondary
+                    .into_iter()
+                    .filter_map(|label| sarif_location(label, sources))
+                    .collect(),
+            }
+        })
+        .collect();
+    let log = SarifLog {
+        version: SARIF_VERSION,
+        schema: SARIF_SCHEMA,
+        runs: [SarifRun {
+            tool: SarifTool {
+                driver: SarifDriver {
+                    name: env!("CARGO_PKG_NAME"),
+                    version: env!("CARGO_PKG_VERSION"),
+                },
+            },
+            column_kind: "utf16CodeUnits",
+            results,
+        }],
+    };
+    serde_json::to_string_pretty(&log).expect("diagnostics serialize to SARIF")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::source::FileId;
+
+    use super::*;
+
+    fn unclosed_parenthesis() -> (Diagnostic, SourceMap) {
+        let mut sources = SourceMap::new();
+        sources.add("script.lox", "var a = 1;\nvar b = (a + 😀;\n");
+        let span = |start, end| Span {
+            file: FileId(0),
+            start,
+            end,
+        };
+        let e = Error::UnclosedParenthesis {
+            span: span(19, 20),
+            close: span(28, 29),
+        };
+        (e.diagnostic(), sources)
+    }
+
+    #[test]
+    fn json_record() {
+        let (diagnostic, sources) = unclosed_parenthesis();
+        let actual: serde_json::Value = serde_json::from_str(&json(&diagnostic, &sources)).unwrap();
+        let expected = serde_json::json!({
+            "severity": "error",
+            "code": "E0004",
+            "message": "unclosed parenthesis",
+            "file": "script.lox",
+            "span": {"start": 19, "end": 20},
+            "range": {"start": {"line": 2, "column": 9}, "end": {"line": 2, "column": 10}},
+            "labels": [
+                {
+                    "file": "script.lox",
+                    "span": {"start": 19, "end": 20},
+                    "range": {"start": {"line": 2, "column": 9}, "end": {"line": 2, "column": 10}},
+                    "message": "this '(' is never closed",
+                    "primary": true,
+                },
+                {
+                    "file": "script.lox",
+                    "span": {"start": 28, "end": 29},
+                    "range": {"start": {"line": 2, "column": 15}, "end": {"line": 2, "column": 16}},
+                    "message": "expected ')' here",
+                    "primary": false,
+                },
+            ],
+            "notes": [],
+            "help": null,
+        });
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn json_label_without_message() {
+        let mut sources = SourceMap::new();
+        sources.add("script.lox", "print \"\\q\";\n");
+        let span = Span {
+            file: FileId(0),
+            start: 7,
+            end: 9,
+        };
+        let diagnostic = Error::InvalidEscape { span }.diagnostic();
+        let actual: serde_json::Value = serde_json::from_str(&json(&diagnostic, &sources)).unwrap();
+        assert_eq!(
+            actual["labels"][0],
+            serde_json::json!({
+                "file": "script.lox",
+                "span": {"start": 7, "end": 9},
+                "range": {"start": {"line": 1, "column": 8}, "end": {"line": 1, "column": 10}},
+                "primary": true,
+            })
+        );
+    }
+
+    #[test]
+    fn sarif_log() {
+        let (diagnostic, sources) = unclosed_parenthesis();
+        let actual: serde_json::Value =
+            serde_json::from_str(&sarif(&[diagnostic], &sources)).unwrap();
+        assert_eq!(actual["version"], "2.1.0");
+        let result = &actual["runs"][0]["results"][0];
+        assert_eq!(result["level"], "error");
+        assert_eq!(result["message"]["text"], "unclosed parenthesis");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "script.lox");
+        let region = &location["region"];
+        assert_eq!(
+            *region,
+            serde_json::json!({
+                "startLine": 2,
+                "startColumn": 9,
+                "endLine": 2,
+                "endColumn": 10,
+                "byteOffset": 19,
+                "byteLength": 1,
+            })
+        );
+        // The emoji before `;` is two UTF-16 code units.
+        let related = &result["relatedLocations"][0];
+        assert_eq!(related["message"]["text"], "expected ')' here");
+        let region = &related["physicalLocation"]["region"];
+        assert_eq!(region["startColumn"], 16);
+    }
+
+    #[test]
+    fn artifact_uris() {
+        assert_eq!(
+            artifact_uri(Utf8Path::new("dir/my script.lox")),
+            "dir/my%20script.lox"
+        );
+        assert_eq!(
+            artifact_uri(Utf8Path::new("a:b#1%.lox")),
+            "a%3Ab%231%25.lox"
+        );
+        assert_eq!(artifact_uri(Utf8Path::new("é.lox")), "%C3%A9.lox");
+        #[cfg(unix)]
+        assert_eq!(
+            artifact_uri(Utf8Path::new("/home/me/a b.lox")),
+            "file:///home/me/a%20b.lox"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::value::Value;
+
+/// A scope of variable bindings, chained to the scope that encloses it.
+#[derive(Debug, Default)]
+pub struct Environment<'src> {
+    values: HashMap<String, Value<'src>>,
+    /// The enclosing scope, or `None` for the global scope.
+    enclosing: Option<Rc<RefCell<Environment<'src>>>>,
+}
+
+impl<'src> Environment<'src> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new scope nested inside `enclosing`.
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment<'src>>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Bind `name` in this scope, shadowing any outer binding and replacing
+    /// any existing binding in this scope.
+    pub fn define(&mut self, name: String, value: Value<'src>) {
+        self.values.insert(name, value);
+    }
+
+    /// Look up `name`, starting in this scope and walking outwards.
+    pub fn get(&self, name: &str) -> Option<Value<'src>> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.enclosing.as_ref()?.borrow().get(name),
+        }
+    }
+
+    /// Look up `name` in the scope `distance` scopes out from this one.
+    pub fn get_at(&self, distance: usize, name: &str) -> Option<Value<'src>> {
+        if distance == 0 {
+            self.values.get(name).cloned()
+        } else {
+            self.enclosing.as_ref()?.borrow().get_at(distance - 1, name)
+        }
+    }
+
+    /// Assign to the innermost existing binding of `name`. Returns `false` if
+    /// `name` isn't bound in any scope.
+    pub fn assign(&mut self, name: &str, value: Value<'src>) -> bool {
+        if let Some(slot) = self.values.get_mut(name) {
+            *slot = value;
+            true
+        } else if let Some(enclosing) = &self.enclosing {
+            enclosing.borrow_mut().assign(name, value)
+        } else {
+            false
+        }
+    }
+
+    /// Assign to `name` in the scope `distance` scopes out from this one.
+    /// Returns `false` if `name` isn't bound in that scope.
+    pub fn assign_at(&mut self, distance: usize, name: &str, value: Value<'src>) -> bool {
+        if distance == 0 {
+            match self.values.get_mut(name) {
+                Some(slot) => {
+                    *slot = value;
+                    true
+                }
+                None => false,
+            }
+        } else if let Some(enclosing) = &self.enclosing {
+            enclosing.borrow_mut().assign_at(distance - 1, name, value)
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shadowing_and_assignment() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a".into(), Value::Number(1.0));
+        globals.borrow_mut().define("b".into(), Value::Number(2.0));
+
+        let mut local = Environment::with_enclosing(Rc::clone(&globals));
+        local.define("a".into(), Value::Bool(true));
+        assert!(local.assign("b", Value::Nil));
+        assert!(!local.assign("c", Value::Nil));
+
+        assert_eq!(local.get("a"), Some(Value::Bool(true)));
+        assert_eq!(globals.borrow().get("a"), Some(Value::Number(1.0)));
+        assert_eq!(globals.borrow().get("b"), Some(Value::Nil));
+        assert_eq!(local.get("c"), None);
+    }
+}
@@ -1,71 +1,259 @@
+use crate::diagnostic::{Diagnostic, Label};
+use crate::source::Span;
+
 #[derive(Clone, Debug, PartialEq, thiserror::Error)]
 pub enum Error {
-    #[error(
-        "invalid character, line {line_number}\n{source_line}\n{:->column_number$}",
-        "^"
-    )]
-    InvalidCharacter {
-        source_line: String,
-        line_number: usize,
-        column_number: usize,
-    },
-    #[error(
-        "unterminated string, line {line_number}\n{source_line}\n{:->column_number$}",
-        "^"
-    )]
-    UnterminatedString {
-        source_line: String,
-        line_number: usize,
-        column_number: usize,
-    },
-    #[error(
-        "unterminated comment, line {line_number}\n{source_line}\n{:->column_number$}",
-        "^"
-    )]
-    UnterminatedBlockComment {
-        source_line: String,
-        line_number: usize,
-        column_number: usize,
-    },
-    #[error(
-        "unclosed parenthesis, line {line_number}\n{source_line}\n{:->column_number$}",
-        "^"
-    )]
-    UnclosedParenthesis {
-        source_line: String,
-        line_number: usize,
-        column_number: usize,
-    },
-    #[error(
-        "parse error, line {line_number}\n{source_line}\n{:->column_number$}",
-        "^"
-    )]
-    ParseError {
-        source_line: String,
-        line_number: usize,
-        column_number: usize,
+    #[error("invalid character")]
+    InvalidCharacter { span: Span },
+    #[error("unterminated string")]
+    UnterminatedString { span: Span },
+    #[error("unterminated comment")]
+    UnterminatedBlockComment { span: Span },
+    #[error("invalid number literal")]
+    InvalidNumber { span: Span },
+    #[error("invalid escape sequence")]
+    InvalidEscape { span: Span },
+    #[error("unterminated string interpolation")]
+    UnterminatedInterpolation { span: Span },
+    /// Points at the `(`, with `close` where the `)` was expected.
+    #[error("unclosed parenthesis")]
+    UnclosedParenthesis { span: Span, close: Span },
+    #[error("parse error")]
+    ParseError { span: Span },
+    #[error("unexpected token")]
+    UnexpectedToken { span: Span },
+    #[error("expected {expected}")]
+    Expected { expected: &'static str, span: Span },
+    #[error("can't have more than {max} arguments")]
+    TooManyArguments { max: usize, span: Span },
+    #[error("can't have more than {max} parameters")]
+    TooManyParameters { max: usize, span: Span },
+    #[error("invalid assignment target")]
+    InvalidAssignmentTarget { span: Span },
+    #[error("can't read local variable in its own initializer")]
+    ReadLocalInOwnInitializer { span: Span },
+    #[error("already a variable named '{name}' in this scope")]
+    DuplicateLocal { name: String, span: Span },
+    #[error("can't return from top-level code")]
+    ReturnOutsideFunction { span: Span },
+    #[error("can't return a value from an initializer")]
+    ReturnValueFromInitializer { span: Span },
+    #[error("can't use 'this' outside of a class")]
+    ThisOutsideClass { span: Span },
+    #[error("can't use 'super' outside of a class")]
+    SuperOutsideClass { span: Span },
+    #[error("can't use 'super' in a class with no superclass")]
+    SuperWithoutSuperclass { span: Span },
+    #[error("a class can't inherit from itself")]
+    InheritFromSelf { span: Span },
+    #[error("operand must be a number")]
+    OperandMustBeNumber { span: Span },
+    #[error("operands must be numbers")]
+    OperandsMustBeNumbers { span: Span },
+    #[error("operands must be two numbers or two strings")]
+    OperandsMustBeNumbersOrStrings { span: Span },
+    #[error("undefined variable '{name}'")]
+    UndefinedVariable { name: String, span: Span },
+    #[error("can only call functions and classes")]
+    NotCallable { span: Span },
+    #[error("expected {expected} arguments but got {found}")]
+    ArityMismatch {
+        expected: usize,
+        found: usize,
+        span: Span,
     },
+    #[error("only instances have properties")]
+    OnlyInstancesHaveProperties { span: Span },
+    #[error("only instances have fields")]
+    OnlyInstancesHaveFields { span: Span },
+    #[error("undefined property '{name}'")]
+    UndefinedProperty { name: String, span: Span },
+    #[error("superclass must be a class")]
+    SuperclassMustBeClass { span: Span },
+    #[error("stack overflow")]
+    StackOverflow { span: Span },
+    #[error("internal error: formatting would change what the code means")]
+    FormattingChangedMeaning { span: Span },
+}
+
+/// Match the span of every variant of an [`Error`], by reference or mutable
+/// reference to match the error.
+macro_rules! primary_span {
+    ($error:expr) => {
+        match $error {
+            Error::InvalidCharacter { span }
+            | Error::UnterminatedString { span }
+            | Error::UnterminatedBlockComment { span }
+            | Error::InvalidNumber { span }
+            | Error::InvalidEscape { span }
+            | Error::UnterminatedInterpolation { span }
+            | Error::UnclosedParenthesis { span, .. }
+            | Error::ParseError { span }
+            | Error::UnexpectedToken { span }
+            | Error::Expected { span, .. }
+            | Error::TooManyArguments { span, .. }
+            | Error::TooManyParameters { span, .. }
+            | Error::InvalidAssignmentTarget { span }
+            | Error::ReadLocalInOwnInitializer { span }
+            | Error::DuplicateLocal { span, .. }
+            | Error::ReturnOutsideFunction { span }
+            | Error::ReturnValueFromInitializer { span }
+            | Error::ThisOutsideClass { span }
+            | Error::SuperOutsideClass { span }
+            | Error::SuperWithoutSuperclass { span }
+            | Error::InheritFromSelf { span }
+            | Error::OperandMustBeNumber { span }
+            | Error::OperandsMustBeNumbers { span }
+            | Error::OperandsMustBeNumbersOrStrings { span }
+            | Error::UndefinedVariable { span, .. }
+            | Error::NotCallable { span }
+            | Error::ArityMismatch { span, .. }
+            | Error::OnlyInstancesHaveProperties { span }
+            | Error::OnlyInstancesHaveFields { span }
+            | Error::UndefinedProperty { span, .. }
+            | Error::SuperclassMustBeClass { span }
+            | Error::StackOverflow { span }
+            | Error::FormattingChangedMeaning { span } => span,
+        }
+    };
+}
+
+impl Error {
+    /// The span of source code the error points at.
+    pub fn span(&self) -> &Span {
+        primary_span!(self)
+    }
+
+    /// Returns the error with its spans moved by `f`, like to where an edit
+    /// to the source moved the code.
+    pub fn map_spans(mut self, mut f: impl FnMut(&Span) -> Span) -> Self {
+        if let Error::UnclosedParenthesis { close, .. } = &mut self {
+            *close = f(close);
+        }
+        let span: &mut Span = primary_span!(&mut self);
+        *span = f(span);
+        self
+    }
+
+    /// The error's stable code, like `E0001`. Codes are never reused or
+    /// renumbered; `lox explain <code>` describes each one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::InvalidCharacter { .. } => "E0001",
+            Error::UnterminatedString { .. } => "E0002",
+            Error::UnterminatedBlockComment { .. } => "E0003",
+            Error::UnclosedParenthesis { .. } => "E0004",
+            Error::ParseError { .. } => "E0005",
+            Error::InvalidNumber { .. } => "E0006",
+            Error::InvalidEscape { .. } => "E0007",
+            Error::UnterminatedInterpolation { .. } => "E0008",
+            Error::UnexpectedToken { .. } => "E0009",
+            Error::Expected { .. } => "E0010",
+            Error::TooManyArguments { .. } => "E0011",
+            Error::TooManyParameters { .. } => "E0012",
+            Error::InvalidAssignmentTarget { .. } => "E0013",
+            Error::ReadLocalInOwnInitializer { .. } => "E0014",
+            Error::DuplicateLocal { .. } => "E0015",
+            Error::ReturnOutsideFunction { .. } => "E0016",
+            Error::ReturnValueFromInitializer { .. } => "E0017",
+            Error::ThisOutsideClass { .. } => "E0018",
+            Error::SuperOutsideClass { .. } => "E0019",
+            Error::SuperWithoutSuperclass { .. } => "E0020",
+            Error::InheritFromSelf { .. } => "E0021",
+            Error::OperandMustBeNumber { .. } => "E0022",
+            Error::OperandsMustBeNumbers { .. } => "E0023",
+            Error::OperandsMustBeNumbersOrStrings { .. } => "E0024",
+            Error::UndefinedVariable { .. } => "E0025",
+            Error::NotCallable { .. } => "E0026",
+            Error::ArityMismatch { .. } => "E0027",
+            Error::OnlyInstancesHaveProperties { .. } => "E0028",
+            Error::OnlyInstancesHaveFields { .. } => "E0029",
+            Error::UndefinedProperty { .. } => "E0030",
+            Error::SuperclassMustBeClass { .. } => "E0031",
+            Error::StackOverflow { .. } => "E0032",
+            Error::FormattingChangedMeaning { .. } => "E0033",
+        }
+    }
+
+    /// Describe the error as a [`Diagnostic`], labelling the source it points
+    /// at.
+    pub fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(self.to_string()).with_code(self.code());
+        match self {
+            Error::UnclosedParenthesis { span, close } => diagnostic
+                .with_label(Label::primary(span.clone()).with_message("this '(' is never closed"))
+                .with_label(Label::secondary(close.clone()).with_message("expected ')' here")),
+            Error::UnterminatedInterpolation { span } => diagnostic
+                .with_label(Label::primary(span.clone()).with_message("this hole is never closed"))
+                .with_help("close the hole with '}'"),
+            Error::InvalidEscape { span } => diagnostic
+                .with_label(Label::primary(span.clone()))
+                .with_note(r#"valid escapes are \n, \t, \\, \", \0 and \u{XXXX}"#),
+            Error::FormattingChangedMeaning { span } => diagnostic
+                .with_label(Label::primary(span.clone()))
+                .with_note("this is a bug in the formatter, so the code was left as it was"),
+            _ => diagnostic.with_label(Label::primary(self.span().clone())),
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::diagnostic::Renderer;
+    use crate::source::{FileId, SourceMap};
+
     use super::*;
     use indoc::indoc;
 
     #[test]
     fn invalid_character() {
-        let source_line = String::from("class @bad");
+        let mut sources = SourceMap::new();
+        sources.add("bad.lox", "class @bad");
         let e = Error::InvalidCharacter {
-            source_line,
-            line_number: 1,
-            column_number: 7,
+            span: Span {
+                file: FileId(0),
+                start: 6,
+                end: 7,
+            },
         };
-        let actual = format!("{e}");
-        let expected = indoc! {r#"
-            invalid character, line 1
-            class @bad
-            ------^"#
+        let actual = Renderer::plain().render(&e.diagnostic(), &sources);
+        let expected = indoc! {"
+            error[E0001]: invalid character
+             --> bad.lox:1:7
+              |
+            1 | class @bad
+              |       ^
+        "};
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn multiline_source() {
+        let mut sources = SourceMap::new();
+        sources.add("first.lox", "print 1;");
+        sources.add("src/second.lox", "var a = 1;\nvar b = (a + 2;\n");
+        let e = Error::UnclosedParenthesis {
+            span: Span {
+                file: FileId(1),
+                start: 19,
+                end: 20,
+            },
+            close: Span {
+                file: FileId(1),
+                start: 25,
+                end: 26,
+            },
         };
+        let actual = Renderer::plain().render(&e.diagnostic(), &sources);
+        let expected = indoc! {"
+            error[E0004]: unclosed parenthesis
+             --> src/second.lox:2:9
+              |
+            2 | var b = (a + 2;
+              |         ^     - expected ')' here
+              |         |
+              |         this '(' is never closed
+        "};
         assert_eq!(actual, expected)
     }
 }
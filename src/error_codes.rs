@@ -0,0 +1,54 @@
+//! Long-form explanations of error codes, for `lox explain`.
+
+/// Declares the table of codes, each explained by a Markdown file of the same
+/// name in `error_codes/`.
+macro_rules! error_codes {
+    ($($code:ident),* $(,)?) => {
+        /// Every error code with its explanation, in order.
+        pub const ERROR_CODES: &[(&str, &str)] = &[
+            $((
+                stringify!($code),
+                include_str!(concat!("error_codes/", stringify!($code), ".md")),
+            ),)*
+        ];
+    };
+}
+
+error_codes! {
+    E0001, E0002, E0003, E0004, E0005, E0006, E0007, E0008, E0009, E0010,
+    E0011, E0012, E0013, E0014, E0015, E0016, E0017, E0018, E0019, E0020,
+    E0021, E0022, E0023, E0024, E0025, E0026, E0027, E0028, E0029, E0030,
+    E0031, E0032, E0033,
+}
+
+/// Returns the explanation of `code`, like `E0001`. Case is ignored.
+pub fn explain(code: &str) -> Option<&'static str> {
+    ERROR_CODES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(code))
+        .map(|&(_, explanation)| explanation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_sequential() {
+        for (i, (code, _)) in ERROR_CODES.iter().enumerate() {
+            assert_eq!(*code, format!("E{:04}", i + 1));
+        }
+    }
+
+    #[test]
+    fn explanations_have_examples() {
+        for (code, explanation) in ERROR_CODES {
+            assert!(
+                explanation.contains("Erroneous code example:\n\n    "),
+                "{code} has no example"
+            );
+        }
+        assert_eq!(explain("e0004"), explain("E0004"));
+        assert!(explain("E9999").is_none());
+    }
+}
@@ -1,24 +1,69 @@
 use std::fmt;
 
+use crate::source::Span;
 use crate::token::Token;
 
 #[derive(Debug)]
 pub enum Expr<'a> {
+    Assign {
+        name: &'a Token,
+        value: Box<Expr<'a>>,
+    },
     Binary {
         left: Box<Expr<'a>>,
         operator: &'a Token,
         right: Box<Expr<'a>>,
     },
+    Call {
+        callee: Box<Expr<'a>>,
+        /// The closing parenthesis, where call errors are reported.
+        paren: &'a Token,
+        arguments: Vec<Expr<'a>>,
+    },
+    /// Where an expression couldn't be parsed, covering the tokens skipped,
+    /// or empty at the token where one was missing.
+    Error {
+        span: Span,
+    },
+    Get {
+        object: Box<Expr<'a>>,
+        name: &'a Token,
+    },
     Grouping {
         expression: Box<Expr<'a>>,
     },
+    /// An interpolated string, like `"Hello, ${name}!"`. Its parts alternate
+    /// between string segment literals and the expressions in its holes.
+    Interpolation {
+        parts: Vec<Expr<'a>>,
+    },
     Literal {
         value: &'a Token,
     },
+    Logical {
+        left: Box<Expr<'a>>,
+        operator: &'a Token,
+        right: Box<Expr<'a>>,
+    },
+    Set {
+        object: Box<Expr<'a>>,
+        name: &'a Token,
+        value: Box<Expr<'a>>,
+    },
+    Super {
+        keyword: &'a Token,
+        method: &'a Token,
+    },
+    This {
+        keyword: &'a Token,
+    },
     Unary {
         operator: &'a Token,
         right: Box<Expr<'a>>,
     },
+    Variable {
+        name: &'a Token,
+    },
 }
 
 /// Display Expr in Polish notation.
@@ -27,14 +72,46 @@ pub enum Expr<'a> {
 impl fmt::Display for Expr<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self {
+            Expr::Assign { name, value } => write!(f, "(= {name} {value})"),
             Expr::Binary {
                 left,
                 operator,
                 right,
             } => write!(f, "({operator} {left} {right})"),
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                write!(f, "(call {callee}")?;
+                for argument in arguments {
+                    write!(f, " {argument}")?;
+                }
+                f.write_str(")")
+            }
+            Expr::Error { .. } => f.write_str("(error)"),
+            Expr::Get { object, name } => write!(f, "(. {object} {name})"),
             Expr::Grouping { expression } => write!(f, "(group {expression})"),
+            Expr::Interpolation { parts } => {
+                f.write_str("(interpolate")?;
+                for part in parts {
+                    write!(f, " {part}")?;
+                }
+                f.write_str(")")
+            }
             Expr::Literal { value } => write!(f, "{value}"),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => write!(f, "({operator} {left} {right})"),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => write!(f, "(= (. {object} {name}) {value})"),
+            Expr::Super { method, .. } => write!(f, "(super {method})"),
+            Expr::This { .. } => f.write_str("this"),
             Expr::Unary { operator, right } => write!(f, "({operator} {right})"),
+            Expr::Variable { name } => write!(f, "{name}"),
         }
     }
 }
@@ -42,6 +119,13 @@ impl fmt::Display for Expr<'_> {
 impl PartialEq for Expr<'_> {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
+            (
+                Expr::Assign { name, value },
+                Expr::Assign {
+                    name: other_name,
+                    value: other_value,
+                },
+            ) => name == other_name && value == other_value,
             (
                 Expr::Binary {
                     left,
@@ -54,13 +138,73 @@ impl PartialEq for Expr<'_> {
                     right: other_right,
                 },
             ) => left == other_left && operator == other_operator && right == other_right,
+            (
+                Expr::Call {
+                    callee,
+                    paren,
+                    arguments,
+                },
+                Expr::Call {
+                    callee: other_callee,
+                    paren: other_paren,
+                    arguments: other_arguments,
+                },
+            ) => callee == other_callee && paren == other_paren && arguments == other_arguments,
+            (Expr::Error { span }, Expr::Error { span: other_span }) => span == other_span,
+            (
+                Expr::Get { object, name },
+                Expr::Get {
+                    object: other_object,
+                    name: other_name,
+                },
+            ) => object == other_object && name == other_name,
             (
                 Expr::Grouping { expression },
                 Expr::Grouping {
                     expression: other_expression,
                 },
             ) => expression == other_expression,
+            (Expr::Interpolation { parts }, Expr::Interpolation { parts: other_parts }) => {
+                parts == other_parts
+            }
             (Expr::Literal { value }, Expr::Literal { value: other_value }) => value == other_value,
+            (
+                Expr::Logical {
+                    left,
+                    operator,
+                    right,
+                },
+                Expr::Logical {
+                    left: other_left,
+                    operator: other_operator,
+                    right: other_right,
+                },
+            ) => left == other_left && operator == other_operator && right == other_right,
+            (
+                Expr::Set {
+                    object,
+                    name,
+                    value,
+                },
+                Expr::Set {
+                    object: other_object,
+                    name: other_name,
+                    value: other_value,
+                },
+            ) => object == other_object && name == other_name && value == other_value,
+            (
+                Expr::Super { keyword, method },
+                Expr::Super {
+                    keyword: other_keyword,
+                    method: other_method,
+                },
+            ) => keyword == other_keyword && method == other_method,
+            (
+                Expr::This { keyword },
+                Expr::This {
+                    keyword: other_keyword,
+                },
+            ) => keyword == other_keyword,
             (
                 Expr::Unary { operator, right },
                 Expr::Unary {
@@ -68,6 +212,7 @@ impl PartialEq for Expr<'_> {
                     right: other_right,
                 },
             ) => operator == other_operator && right == other_right,
+            (Expr::Variable { name }, Expr::Variable { name: other_name }) => name == other_name,
             _ => false,
         }
     }
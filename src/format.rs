@@ -0,0 +1,746 @@
+//! The `lox fmt` source formatter.
+//!
+//! Code is laid out in one canonical style: two-space indentation, one
+//! statement per line, braces on the line that opens them, and single spaces
+//! around binary operators. Comments are kept where they were, relative to the
+//! code around them, and single blank lines between statements are kept too.
+
+use crate::error::Error;
+use crate::expr::Expr;
+use crate::parser::Parser;
+use crate::source::{Source, Span};
+use crate::stack;
+use crate::stmt::{FunctionDecl, Stmt};
+use crate::syntax::{self, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken, Trivia};
+use crate::token::{Keyword, Token, TokenKind};
+
+const INDENT: &str = "  ";
+
+/// Format `source`, or return its syntax errors. Code with errors isn't
+/// formatted, since its structure isn't known.
+///
+/// If the formatted code would mean something else, which is a bug, an
+/// internal error is returned instead.
+pub fn format(source: &Source) -> Result<String, Vec<Error>> {
+    let parse = syntax::parse_with_file(source.as_str(), source.id());
+    if !parse.errors().is_empty() {
+        return Err(parse.errors().to_vec());
+    }
+
+    let mut printer = Printer::default();
+    printer.node(&parse.syntax());
+    let formatted = printer.finish();
+    if !same_program(source.as_str(), &formatted) {
+        return Err(vec![Error::FormattingChangedMeaning {
+            span: Span {
+                file: source.id(),
+                start: 0,
+                end: 0,
+            },
+        }]);
+    }
+    Ok(formatted)
+}
+
+/// Whitespace to write before the next text. Larger gaps win.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+enum Gap {
+    #[default]
+    None,
+    Space,
+    Line,
+    BlankLine,
+}
+
+/// Writes a syntax tree out, laying out its tokens and carrying over their
+/// comments.
+#[derive(Debug, Default)]
+struct Printer {
+    out: String,
+    indent: usize,
+    pending: Gap,
+    /// Whether a comment broke a line the layout didn't, so the code after it
+    /// continues one level deeper.
+    continuation: bool,
+}
+
+impl Printer {
+    fn finish(mut self) -> String {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+
+    /// Ask for at least `gap` before the next text, as the layout of the code.
+    fn gap(&mut self, gap: Gap) {
+        if gap >= Gap::Line {
+            self.continuation = false;
+        }
+        self.pending = self.pending.max(gap);
+    }
+
+    /// Break the line for a comment. If the layout doesn't break it already,
+    /// the code continues on the next line.
+    fn break_line(&mut self) {
+        if self.pending < Gap::Line && !self.out.is_empty() {
+            self.pending = Gap::Line;
+            self.continuation = true;
+        }
+    }
+
+    /// Keep a blank line from the source, if the layout breaks the line here.
+    fn blank_line(&mut self) {
+        if self.pending == Gap::Line && !self.continuation {
+            self.pending = Gap::BlankLine;
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        if !self.out.is_empty() {
+            match self.pending {
+                Gap::None => {}
+                Gap::Space => self.out.push(' '),
+                Gap::Line | Gap::BlankLine => {
+                    // No blank lines at the start of a block.
+                    if self.pending == Gap::BlankLine && !self.out.ends_with('{') {
+                        self.out.push('\n');
+                    }
+                    self.out.push('\n');
+                    let depth = self.indent + usize::from(self.continuation);
+                    self.out.push_str(&INDENT.repeat(depth));
+                }
+            }
+        }
+        self.pending = Gap::None;
+        self.out.push_str(text);
+    }
+
+    fn element(&mut self, element: &SyntaxElement) {
+        match element {
+            SyntaxElement::Node(node) => self.node(node),
+            SyntaxElement::Token(token) => self.token(token),
+        }
+    }
+
+    fn token(&mut self, token: &SyntaxToken) {
+        self.leading_trivia(token.leading());
+        // The space after a comment before this token, which goes right after
+        // what's before it.
+        if self.pending == Gap::Space && hugs_previous(token.kind()) {
+            self.pending = Gap::None;
+        }
+        self.write(token.text());
+        self.trailing_trivia(token.trailing());
+    }
+
+    /// Write the comments before a token, each starting a line. Leading trivia
+    /// always follows the end of a line, so a newline in it is a blank line.
+    fn leading_trivia(&mut self, trivia: &[Trivia]) {
+        // Whether the layout already starts a line here, so breaking lines for
+        // comments doesn't make the code a continuation.
+        let own_line = self.pending >= Gap::Line || self.out.is_empty();
+        let new_line = |printer: &mut Self| {
+            if own_line {
+                printer.pending = printer.pending.max(Gap::Line);
+            } else {
+                printer.break_line();
+            }
+        };
+
+        let mut line_ended = true;
+        for trivia in trivia {
+            match trivia.kind() {
+                TokenKind::Whitespace => {
+                    let newlines = trivia.text().matches('\n').count();
+                    if newlines > 1 || newlines == 1 && line_ended {
+                        self.blank_line();
+                    }
+                    if newlines > 0 && !line_ended {
+                        new_line(self);
+                        line_ended = true;
+                    }
+                }
+                kind => {
+                    new_line(self);
+                    self.write(&comment_text(trivia));
+                    line_ended = false;
+                    if kind == TokenKind::LineComment {
+                        new_line(self);
+                    } else {
+                        self.gap(Gap::Space);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write the comments after a token, on its line.
+    fn trailing_trivia(&mut self, trivia: &[Trivia]) {
+        for trivia in trivia {
+            match trivia.kind() {
+                TokenKind::Whitespace => {}
+                kind => {
+                    self.gap(Gap::Space);
+                    self.write(&comment_text(trivia));
+                    if kind == TokenKind::LineComment {
+                        self.break_line();
+                    } else {
+                        self.gap(Gap::Space);
+                    }
+                }
+            }
+        }
+    }
+
+    fn node(&mut self, node: &SyntaxNode) {
+        stack::grow(|| {
+            let elements: Vec<_> = node.children_with_tokens().collect();
+            match node.kind() {
+                SyntaxKind::Program => {
+                    for element in &elements {
+                        self.gap(Gap::Line);
+                        match element {
+                            // Eof, which only has trivia.
+                            SyntaxElement::Token(token) => self.leading_trivia(token.leading()),
+                            SyntaxElement::Node(node) => self.node(node),
+                        }
+                    }
+                }
+                SyntaxKind::Block => self.braced(&elements),
+                SyntaxKind::ClassDecl => {
+                    let open = elements
+                        .iter()
+                        .position(|element| is_token(element, TokenKind::LeftBrace))
+                        .unwrap_or(elements.len());
+                    let (header, body) = elements.split_at(open);
+                    self.inline(node.kind(), header);
+                    self.gap(Gap::Space);
+                    self.braced(body);
+                }
+                kind => self.inline(kind, &elements),
+            }
+        })
+    }
+
+    /// Write the elements of a node of `kind` on one line.
+    ///
+    /// The exception is an `if` as the body of another statement, with an
+    /// `else` on a line of its own: the `if` starts its own line, indented, so
+    /// the `else` lines up under it and not under the outer statement.
+    fn inline(&mut self, kind: SyntaxKind, elements: &[SyntaxElement]) {
+        for (i, element) in elements.iter().enumerate() {
+            let Some(previous) = i.checked_sub(1).map(|i| &elements[i]) else {
+                self.element(element);
+                continue;
+            };
+            self.gap(spacing(kind, previous, element));
+            if is_token(previous, TokenKind::RightParen) && breaks_before_else(element) {
+                self.gap(Gap::Line);
+                self.indent += 1;
+                self.element(element);
+                self.indent -= 1;
+            } else {
+                self.element(element);
+            }
+        }
+    }
+
+    /// Write `{`, the elements between it and the `}` ending `elements`, one
+    /// per line and indented, and the `}`.
+    fn braced(&mut self, elements: &[SyntaxElement]) {
+        let [open, items @ .., close] = elements else {
+            unreachable!("formatted code has no errors, so braces are balanced");
+        };
+        let (SyntaxElement::Token(open), SyntaxElement::Token(close)) = (open, close) else {
+            unreachable!("a block is between '{{' and '}}'");
+        };
+        self.leading_trivia(open.leading());
+        // A line comment before the brace broke the line, but the brace still
+        // lines up with the code it opens a block of, not as a continuation.
+        self.continuation = false;
+        self.write(open.text());
+        self.trailing_trivia(open.trailing());
+        self.indent += 1;
+        for item in items {
+            self.gap(Gap::Line);
+            self.element(item);
+        }
+        let comments = close.leading().iter().any(is_comment);
+        if !items.is_empty() || comments {
+            self.gap(Gap::Line);
+        }
+        self.leading_trivia(close.leading());
+        self.indent -= 1;
+        if !items.is_empty() || comments || self.pending >= Gap::Line {
+            // No blank lines at the end of a block.
+            self.pending = Gap::None;
+            self.gap(Gap::Line);
+        }
+        self.write(close.text());
+        self.trailing_trivia(close.trailing());
+    }
+}
+
+/// Returns the whitespace between adjacent elements of a node of `kind`.
+fn spacing(kind: SyntaxKind, previous: &SyntaxElement, next: &SyntaxElement) -> Gap {
+    let token_kind = |element: &SyntaxElement| match element {
+        SyntaxElement::Token(token) => Some(token.kind()),
+        SyntaxElement::Node(_) => None,
+    };
+    match (kind, token_kind(previous), token_kind(next)) {
+        // Two negations written together would read as `--`.
+        (SyntaxKind::Unary, Some(TokenKind::Minus), None)
+            if matches!(next, SyntaxElement::Node(node)
+                if node.first_token().is_some_and(|token| token.kind() == TokenKind::Minus)) =>
+        {
+            Gap::Space
+        }
+        (
+            SyntaxKind::Interpolation | SyntaxKind::Get | SyntaxKind::Super | SyntaxKind::Unary,
+            ..,
+        ) => Gap::None,
+        (_, _, Some(kind)) if hugs_previous(kind) => Gap::None,
+        (_, Some(TokenKind::LeftParen | TokenKind::Dot), _) => Gap::None,
+        // Between a callee or function name and its parentheses.
+        _ if matches!(next, SyntaxElement::Node(node)
+            if matches!(node.kind(), SyntaxKind::ArgList | SyntaxKind::ParamList)) =>
+        {
+            Gap::None
+        }
+        // An `else` after a statement that isn't a block starts its own line.
+        (SyntaxKind::IfStmt, None, Some(TokenKind::Keyword(_))) if !matches!(previous, SyntaxElement::Node(node) if node.kind() == SyntaxKind::Block) => {
+            Gap::Line
+        }
+        _ => Gap::Space,
+    }
+}
+
+/// Returns true if a token of `kind` goes right after the code before it.
+fn hugs_previous(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Semicolon | TokenKind::Comma | TokenKind::RightParen | TokenKind::Dot
+    )
+}
+
+/// Returns true if `element` is an `if` statement with an `else` starting its
+/// own line, after a branch that isn't a block.
+fn breaks_before_else(element: &SyntaxElement) -> bool {
+    let SyntaxElement::Node(node) = element else {
+        return false;
+    };
+    let children: Vec<_> = node.children_with_tokens().collect();
+    let Some(else_at) = children
+        .iter()
+        .position(|child| is_token(child, TokenKind::Keyword(Keyword::Else)))
+    else {
+        return false;
+    };
+    let then_block = matches!(&children[else_at - 1],
+        SyntaxElement::Node(node) if node.kind() == SyntaxKind::Block);
+    node.kind() == SyntaxKind::IfStmt
+        && (!then_block || children.get(else_at + 1).is_some_and(breaks_before_else))
+}
+
+fn is_token(element: &SyntaxElement, kind: TokenKind) -> bool {
+    matches!(element, SyntaxElement::Token(token) if token.kind() == kind)
+}
+
+fn is_comment(trivia: &Trivia) -> bool {
+    trivia.kind() != TokenKind::Whitespace
+}
+
+/// Returns a comment's text with line endings normalized, and without the
+/// trailing whitespace a line comment runs to.
+fn comment_text(trivia: &Trivia) -> String {
+    match trivia.kind() {
+        TokenKind::LineComment => trivia.text().trim_end().to_owned(),
+        _ => trivia.text().replace("\r\n", "\n"),
+    }
+}
+
+/// Return true if `before` and `after` parse to the same program. Tokens are
+/// compared by kind and text, not position.
+fn same_program(before: &str, after: &str) -> bool {
+    let (before_syntax, after_syntax) = (syntax::parse(before), syntax::parse(after));
+    let before_tokens = before_syntax.tokens();
+    let after_tokens = after_syntax.tokens();
+    let (Ok(before_program), Ok(after_program)) = (
+        Parser::new(&before_syntax, &before_tokens).parse(),
+        Parser::new(&after_syntax, &after_tokens).parse(),
+    ) else {
+        return false;
+    };
+    Same { before, after }.stmts(&before_program, &after_program)
+}
+
+/// Compares syntax trees from two sources.
+struct Same<'s> {
+    before: &'s str,
+    after: &'s str,
+}
+
+impl Same<'_> {
+    fn token(&self, a: &Token, b: &Token) -> bool {
+        a.kind() == b.kind() && a.lexeme(self.before) == b.lexeme(self.after)
+    }
+
+    fn tokens(&self, a: &[&Token], b: &[&Token]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.token(a, b))
+    }
+
+    fn stmts(&self, a: &[Stmt], b: &[Stmt]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.stmt(a, b))
+    }
+
+    fn function(&self, a: &FunctionDecl, b: &FunctionDecl) -> bool {
+        self.token(a.name, b.name)
+            && self.tokens(&a.params, &b.params)
+            && self.stmts(&a.body, &b.body)
+    }
+
+    fn stmt(&self, a: &Stmt, b: &Stmt) -> bool {
+        stack::grow(|| match (a, b) {
+            (Stmt::Block { statements: a }, Stmt::Block { statements: b }) => self.stmts(a, b),
+            (
+                Stmt::Class {
+                    name,
+                    superclass,
+                    methods,
+                },
+                Stmt::Class {
+                    name: other_name,
+                    superclass: other_superclass,
+                    methods: other_methods,
+                },
+            ) => {
+                self.token(name, other_name)
+                    && self.optional_expr(superclass, other_superclass)
+                    && methods.len() == other_methods.len()
+                    && methods
+                        .iter()
+                        .zip(other_methods)
+                        .all(|(a, b)| self.function(a, b))
+            }
+            (Stmt::Expression { expression: a }, Stmt::Expression { expression: b })
+            | (Stmt::Print { expression: a }, Stmt::Print { expression: b }) => self.expr(a, b),
+            (Stmt::Function { declaration: a }, Stmt::Function { declaration: b }) => {
+                self.function(a, b)
+            }
+            (
+                Stmt::If {
+                    condition,
+                    then_branch,
+                    else_branch,
+                },
+                Stmt::If {
+                    condition: other_condition,
+                    then_branch: other_then_branch,
+                    else_branch: other_else_branch,
+                },
+            ) => {
+                self.expr(condition, other_condition)
+                    && self.stmt(then_branch, other_then_branch)
+                    && match (else_branch, other_else_branch) {
+                        (Some(a), Some(b)) => self.stmt(a, b),
+                        (a, b) => a.is_none() && b.is_none(),
+                    }
+            }
+            (
+                Stmt::Return { keyword, value },
+                Stmt::Return {
+                    keyword: other_keyword,
+                    value: other_value,
+                },
+            ) => self.token(keyword, other_keyword) && self.optional_expr(value, other_value),
+            (
+                Stmt::Var { name, initializer },
+                Stmt::Var {
+                    name: other_name,
+                    initializer: other_initializer,
+                },
+            ) => self.token(name, other_name) && self.optional_expr(initializer, other_initializer),
+            (
+                Stmt::While { condition, body },
+                Stmt::While {
+                    condition: other_condition,
+                    body: other_body,
+                },
+            ) => self.expr(condition, other_condition) && self.stmt(body, other_body),
+            _ => false,
+        })
+    }
+
+    fn optional_expr(&self, a: &Option<Box<Expr>>, b: &Option<Box<Expr>>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => self.expr(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+
+    fn exprs(&self, a: &[Expr], b: &[Expr]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.expr(a, b))
+    }
+
+    fn expr(&self, a: &Expr, b: &Expr) -> bool {
+        stack::grow(|| match (a, b) {
+            (
+                Expr::Assign { name, value },
+                Expr::Assign {
+                    name: other_name,
+                    value: other_value,
+                },
+            ) => self.token(name, other_name) && self.expr(value, other_value),
+            (
+                Expr::Binary {
+                    left,
+                    operator,
+                    right,
+                },
+                Expr::Binary {
+                    left: other_left,
+                    operator: other_operator,
+                    right: other_right,
+                },
+            )
+            | (
+                Expr::Logical {
+                    left,
+                    operator,
+                    right,
+                },
+                Expr::Logical {
+                    left: other_left,
+                    operator: other_operator,
+                    right: other_right,
+                },
+            ) => {
+                self.expr(left, other_left)
+                    && self.token(operator, other_operator)
+                    && self.expr(right, other_right)
+            }
+            (
+                Expr::Call {
+                    callee, arguments, ..
+                },
+                Expr::Call {
+                    callee: other_callee,
+                    arguments: other_arguments,
+                    ..
+                },
+            ) => self.expr(callee, other_callee) && self.exprs(arguments, other_arguments),
+            (
+                Expr::Get { object, name },
+                Expr::Get {
+                    object: other_object,
+                    name: other_name,
+                },
+            ) => self.expr(object, other_object) && self.token(name, other_name),
+            (Expr::Grouping { expression: a }, Expr::Grouping { expression: b }) => self.expr(a, b),
+            (Expr::Interpolation { parts: a }, Expr::Interpolation { parts: b }) => {
+                self.exprs(a, b)
+            }
+            (Expr::Literal { value: a }, Expr::Literal { value: b })
+            | (Expr::This { keyword: a }, Expr::This { keyword: b })
+            | (Expr::Variable { name: a }, Expr::Variable { name: b }) => self.token(a, b),
+            (
+                Expr::Set {
+                    object,
+                    name,
+                    value,
+                },
+                Expr::Set {
+                    object: other_object,
+                    name: other_name,
+                    value: other_value,
+                },
+            ) => {
+                self.expr(object, other_object)
+                    && self.token(name, other_name)
+                    && self.expr(value, other_value)
+            }
+            (Expr::Super { method: a, .. }, Expr::Super { method: b, .. }) => self.token(a, b),
+            (
+                Expr::Unary { operator, right },
+                Expr::Unary {
+                    operator: other_operator,
+                    right: other_right,
+                },
+            ) => self.token(operator, other_operator) && self.expr(right, other_right),
+            _ => false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use camino::Utf8Path;
+    use indoc::indoc;
+
+    use super::*;
+
+    fn fmt(source: &str) -> String {
+        format(&Source::from(source)).unwrap()
+    }
+
+    #[test]
+    fn layout() {
+        let source = indoc! {r#"
+            var a=1;print -a+2 ;print - -1;print !!a;
+            fun add(a,b){return a+b;}
+            if (a) print 1; else { print 2; }
+            if(a){}else if(b)print 3;
+            class A<B{init(){super.init( );this.x=nil;} m() {}}
+            for(var i=0;i<3;i=i+1) print i;
+            for(;;){}
+            while(!a and b or c)a=a-1;
+            print "x ${ a + f( 1 ) } y";
+        "#};
+        let expected = indoc! {r#"
+            var a = 1;
+            print -a + 2;
+            print - -1;
+            print !!a;
+            fun add(a, b) {
+              return a + b;
+            }
+            if (a) print 1;
+            else {
+              print 2;
+            }
+            if (a) {} else if (b) print 3;
+            class A < B {
+              init() {
+                super.init();
+                this.x = nil;
+              }
+              m() {}
+            }
+            for (var i = 0; i < 3; i = i + 1) print i;
+            for (;;) {}
+            while (!a and b or c) a = a - 1;
+            print "x ${a + f(1)} y";
+        "#};
+        assert_eq!(fmt(source), expected);
+    }
+
+    #[test]
+    fn blank_lines() {
+        let source = "print 1;\n\n\n\nprint 2;\n{\n\n  print 3;\n\n}\n\n\n";
+        assert_eq!(fmt(source), "print 1;\n\nprint 2;\n{\n  print 3;\n}\n");
+        assert_eq!(fmt(""), "");
+        assert_eq!(fmt("\n\n"), "");
+    }
+
+    #[test]
+    fn comments() {
+        let source = indoc! {"
+            // Leading.
+
+            var a = 1;   // Trailing.
+            /* Block /* nested */ comment. */ print a;
+            print a // Inside.
+              + 1;
+            fun f() {
+                // Alone in a block.
+            }
+            {
+              print 1;
+              // Before the brace.
+            }
+            while (true) // Before a block.
+            { print 1; }
+            f(a /* Before a comma. */ , b);
+            // The end.
+        "};
+        let expected = indoc! {"
+            // Leading.
+
+            var a = 1; // Trailing.
+            /* Block /* nested */ comment. */ print a;
+            print a // Inside.
+              + 1;
+            fun f() {
+              // Alone in a block.
+            }
+            {
+              print 1;
+              // Before the brace.
+            }
+            while (true) // Before a block.
+            {
+              print 1;
+            }
+            f(a /* Before a comma. */, b);
+            // The end.
+        "};
+        assert_eq!(fmt(source), expected);
+    }
+
+    #[test]
+    fn nested_if_else() {
+        let source = indoc! {"
+            if (a) if (b) print 1; else print 2;
+            while (a) if (b) {} else if (c) print 1; else print 2;
+            if (a) if (b) {} else {} else print 3;
+        "};
+        let expected = indoc! {"
+            if (a)
+              if (b) print 1;
+              else print 2;
+            while (a)
+              if (b) {} else if (c) print 1;
+              else print 2;
+            if (a) if (b) {} else {}
+            else print 3;
+        "};
+        assert_eq!(fmt(source), expected);
+    }
+
+    #[test]
+    fn line_endings() {
+        assert_eq!(
+            fmt("print 1; // one \r\n/* two\r\n */\r\nprint 2;\r\n"),
+            "print 1; // one\n/* two\n */\nprint 2;\n"
+        );
+    }
+
+    #[test]
+    fn idempotent() {
+        let examples = Utf8Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        for entry in fs::read_dir(examples).unwrap() {
+            let source = fs::read_to_string(entry.unwrap().path()).unwrap();
+            let formatted = fmt(&source);
+            assert_eq!(fmt(&formatted), formatted);
+        }
+        let sources = [
+            "print a /* one */ /* two */ + /* three */ 1;",
+            "{ // after brace\n}\n{ print 1; } // after block\n",
+            "if (a) print 1; // then\nelse print 2;",
+            "f(a, // first\n  b);",
+        ];
+        for source in sources {
+            let formatted = fmt(source);
+            assert_eq!(fmt(&formatted), formatted, "formatting {source:?}");
+        }
+    }
+
+    #[test]
+    fn same_meaning() {
+        let long = "var aVeryLongName = 1;";
+        assert!(same_program(long, "var  aVeryLongName=1 ;"));
+        assert!(!same_program(long, "var aVeryLongNamf = 1;"));
+        assert!(!same_program("print 1 + 2 * 3;", "print (1 + 2) * 3;"));
+        assert!(!same_program("print 1;", "print 1; print 1;"));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let errors = format(&Source::from("print (1;\nvar = 2;")).unwrap_err();
+        let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, ["unclosed parenthesis", "expected variable name"]);
+    }
+}
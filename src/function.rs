@@ -0,0 +1,73 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::environment::Environment;
+use crate::stmt::FunctionDecl;
+use crate::value::Value;
+
+/// A user-defined function, closed over the scope it was declared in.
+pub struct Function<'src> {
+    pub declaration: &'src FunctionDecl<'src>,
+    pub closure: Rc<RefCell<Environment<'src>>>,
+    /// The source the declaration was scanned from.
+    pub source: &'src str,
+    /// Whether this is a class's `init` method, which always returns `this`.
+    pub is_initializer: bool,
+}
+
+impl<'src> Function<'src> {
+    pub fn name(&self) -> &str {
+        self.declaration.name.lexeme(self.source)
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    /// Bind this method to `instance`, so `this` refers to it in the body.
+    pub fn bind(&self, instance: Value<'src>) -> Function<'src> {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define(String::from("this"), instance);
+        Function {
+            declaration: self.declaration,
+            closure: Rc::new(RefCell::new(environment)),
+            source: self.source,
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+/// Don't walk the closure, it may contain this function.
+impl fmt::Debug for Function<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name())
+            .field("arity", &self.arity())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A function implemented by the interpreter.
+#[derive(Debug, Clone, Copy)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: for<'a> fn(&[Value<'a>]) -> Value<'a>,
+}
+
+/// Native functions defined in the global scope.
+pub const NATIVE_FUNCTIONS: &[NativeFunction] = &[NativeFunction {
+    name: "clock",
+    arity: 0,
+    function: clock,
+}];
+
+/// Seconds since the Unix epoch.
+fn clock<'a>(_arguments: &[Value<'a>]) -> Value<'a> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time before Unix epoch");
+    Value::Number(now.as_secs_f64())
+}
@@ -0,0 +1,942 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::mem;
+use std::rc::Rc;
+
+use crate::class::{Class, Instance};
+use crate::environment::Environment;
+use crate::error::Error;
+use crate::expr::Expr;
+use crate::function::{Function, NATIVE_FUNCTIONS};
+use crate::resolver::{Locals, TokenId};
+use crate::stack;
+use crate::stmt::Stmt;
+use crate::token::{Keyword, Token, TokenKind};
+use crate::value::Value;
+
+/// Tree-walking interpreter.
+pub struct Interpreter<'src> {
+    /// The source the executing tokens were scanned from, used to decode
+    /// lexemes.
+    source: &'src str,
+    /// The outermost scope. Global variables persist across calls to
+    /// [`Interpreter::interpret`], so a REPL can run one line at a time.
+    globals: Rc<RefCell<Environment<'src>>>,
+    /// The innermost scope.
+    environment: Rc<RefCell<Environment<'src>>>,
+    /// Scope depths of local variable references, from the
+    /// [`Resolver`](crate::resolver::Resolver).
+    locals: Locals,
+    /// Where `print` statements write.
+    out: Box<dyn Write + 'src>,
+    /// How many calls are running, up to [`MAX_CALL_DEPTH`].
+    call_depth: usize,
+}
+
+/// The most calls that can run at once, so unbounded recursion is a runtime
+/// error instead of overflowing the interpreter's own stack.
+const MAX_CALL_DEPTH: usize = 2048;
+
+/// Why execution of a statement stopped early.
+enum Unwind<'src> {
+    /// A runtime error.
+    Error(Error),
+    /// A `return` statement, unwinding to the enclosing call.
+    Return(Value<'src>),
+    /// Output can't be written anymore, like when the reader of a pipe has
+    /// exited, so the program stops quietly.
+    Exit,
+}
+
+impl From<Error> for Unwind<'_> {
+    fn from(error: Error) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+impl Default for Interpreter<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'src> Interpreter<'src> {
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// Create an interpreter that prints to `out` instead of stdout.
+    pub fn with_output(out: impl Write + 'src) -> Self {
+        let mut globals = Environment::new();
+        for native in NATIVE_FUNCTIONS {
+            globals.define(native.name.to_owned(), Value::NativeFunction(*native));
+        }
+        let globals = Rc::new(RefCell::new(globals));
+        Self {
+            source: "",
+            environment: Rc::clone(&globals),
+            globals,
+            locals: Locals::new(),
+            out: Box::new(out),
+            call_depth: 0,
+        }
+    }
+
+    /// Add resolved local variable depths. Variable references that were
+    /// never resolved are looked up in the global scope.
+    pub fn resolve(&mut self, locals: Locals) {
+        self.locals.extend(locals);
+    }
+
+    /// Execute `statements`, which were parsed from tokens scanned from
+    /// `source`.
+    pub fn interpret(
+        &mut self,
+        source: &'src str,
+        statements: &'src [Stmt<'src>],
+    ) -> Result<(), Error> {
+        self.source = source;
+        for statement in statements {
+            match self.execute(statement) {
+                Ok(()) => {}
+                Err(Unwind::Error(e)) => return Err(e),
+                // A top-level return ends the program.
+                Err(Unwind::Return(_) | Unwind::Exit) => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, stmt: &'src Stmt<'src>) -> Result<(), Unwind<'src>> {
+        stack::grow(|| {
+            match stmt {
+                Stmt::Block { statements } => {
+                    let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                    self.execute_block(statements, environment)?;
+                }
+                Stmt::Class {
+                    name,
+                    superclass,
+                    methods,
+                } => {
+                    let superclass = match superclass.as_deref() {
+                        Some(expr @ Expr::Variable { name }) => match self.evaluate(expr)? {
+                            Value::Class(class) => Some(class),
+                            _ => {
+                                return Err(Error::SuperclassMustBeClass {
+                                    span: name.span().clone(),
+                                }
+                                .into());
+                            }
+                        },
+                        Some(expr) => unreachable!("invalid superclass {expr}"),
+                        None => None,
+                    };
+
+                    let name = name.identifier(self.source).into_owned();
+                    self.environment
+                        .borrow_mut()
+                        .define(name.clone(), Value::Nil);
+
+                    // Methods of a subclass close over a scope binding `super`.
+                    let mut closure = Rc::clone(&self.environment);
+                    if let Some(superclass) = &superclass {
+                        let mut environment = Environment::with_enclosing(closure);
+                        environment
+                            .define(String::from("super"), Value::Class(Rc::clone(superclass)));
+                        closure = Rc::new(RefCell::new(environment));
+                    }
+
+                    let methods = methods
+                        .iter()
+                        .map(|declaration| {
+                            let name = declaration.name.identifier(self.source).into_owned();
+                            let method = Function {
+                                declaration,
+                                closure: Rc::clone(&closure),
+                                source: self.source,
+                                is_initializer: name == "init",
+                            };
+                            (name, Rc::new(method))
+                        })
+                        .collect();
+
+                    let class = Class {
+                        name: name.clone(),
+                        superclass,
+                        methods,
+                    };
+                    self.environment
+                        .borrow_mut()
+                        .assign(&name, Value::Class(Rc::new(class)));
+                }
+                Stmt::Error { .. } => unreachable!("programs with syntax errors aren't run"),
+                Stmt::Expression { expression } => {
+                    self.evaluate(expression)?;
+                }
+                Stmt::Function { declaration } => {
+                    let function = Function {
+                        declaration,
+                        closure: Rc::clone(&self.environment),
+                        source: self.source,
+                        is_initializer: false,
+                    };
+                    let name = declaration.name.identifier(self.source).into_owned();
+                    let value = Value::Function(Rc::new(function));
+                    self.environment.borrow_mut().define(name, value);
+                }
+                Stmt::If {
+                    condition,
+                    then_branch,
+                    else_branch,
+                } => {
+                    if self.evaluate(condition)?.is_truthy() {
+                        self.execute(then_branch)?;
+                    } else if let Some(else_branch) = else_branch {
+                        self.execute(else_branch)?;
+                    }
+                }
+                Stmt::Print { expression } => {
+                    let value = self.evaluate(expression)?;
+                    if writeln!(self.out, "{value}").is_err() {
+                        return Err(Unwind::Exit);
+                    }
+                }
+                Stmt::Return { value, .. } => {
+                    let value = match value {
+                        Some(value) => self.evaluate(value)?,
+                        None => Value::Nil,
+                    };
+                    return Err(Unwind::Return(value));
+                }
+                Stmt::Var { name, initializer } => {
+                    let value = match initializer {
+                        Some(initializer) => self.evaluate(initializer)?,
+                        None => Value::Nil,
+                    };
+                    let name = name.identifier(self.source).into_owned();
+                    self.environment.borrow_mut().define(name, value);
+                }
+                Stmt::While { condition, body } => {
+                    while self.evaluate(condition)?.is_truthy() {
+                        self.execute(body)?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Execute `statements` in `environment`, restoring the current scope
+    /// afterwards even if execution unwinds.
+    fn execute_block(
+        &mut self,
+        statements: &'src [Stmt<'src>],
+        environment: Environment<'src>,
+    ) -> Result<(), Unwind<'src>> {
+        let previous = mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = statements.iter().try_for_each(|stmt| self.execute(stmt));
+        self.environment = previous;
+        result
+    }
+
+    fn evaluate(&mut self, expr: &'src Expr<'src>) -> Result<Value<'src>, Unwind<'src>> {
+        stack::grow(|| match expr {
+            Expr::Assign { name, value } => {
+                let value = self.evaluate(value)?;
+                let lexeme = name.identifier(self.source);
+                let assigned = match self.locals.get(&TokenId::from(*name)) {
+                    Some(&distance) => {
+                        self.environment
+                            .borrow_mut()
+                            .assign_at(distance, &lexeme, value.clone())
+                    }
+                    None => self.globals.borrow_mut().assign(&lexeme, value.clone()),
+                };
+                if assigned {
+                    Ok(value)
+                } else {
+                    Err(self.undefined_variable(name).into())
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                Ok(binary(operator, left, right)?)
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let callee = self.evaluate(callee)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.evaluate(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(callee, paren, arguments)
+            }
+            Expr::Error { .. } => unreachable!("programs with syntax errors aren't run"),
+            Expr::Get { object, name } => match self.evaluate(object)? {
+                Value::Instance(instance) => {
+                    let property = name.identifier(self.source);
+                    Instance::get(&instance, &property)
+                        .ok_or_else(|| self.undefined_property(name).into())
+                }
+                _ => Err(Error::OnlyInstancesHaveProperties {
+                    span: name.span().clone(),
+                }
+                .into()),
+            },
+            Expr::Grouping { expression } => self.evaluate(expression),
+            Expr::Interpolation { parts } => {
+                let mut string = String::new();
+                for part in parts {
+                    string += &self.evaluate(part)?.to_string();
+                }
+                Ok(Value::String(Rc::from(string)))
+            }
+            Expr::Literal { value } => Ok(self.literal(value)),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.evaluate(left)?;
+                // Short-circuit, returning the operand that decided the result.
+                let decided = match operator.kind() {
+                    TokenKind::Keyword(Keyword::Or) => left.is_truthy(),
+                    TokenKind::Keyword(Keyword::And) => !left.is_truthy(),
+                    _ => unreachable!("invalid logical operator {operator}"),
+                };
+                if decided {
+                    Ok(left)
+                } else {
+                    self.evaluate(right)
+                }
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                let Value::Instance(instance) = self.evaluate(object)? else {
+                    return Err(Error::OnlyInstancesHaveFields {
+                        span: name.span().clone(),
+                    }
+                    .into());
+                };
+                let value = self.evaluate(value)?;
+                let name = name.identifier(self.source).into_owned();
+                instance.borrow_mut().set(name, value.clone());
+                Ok(value)
+            }
+            Expr::Super { keyword, method } => {
+                // `this` is bound in the scope just inside the one binding
+                // `super`.
+                let distance = self.locals.get(&TokenId::from(*keyword)).copied();
+                let (superclass, this) = match distance {
+                    Some(distance) if distance > 0 => {
+                        let environment = self.environment.borrow();
+                        (
+                            environment.get_at(distance, "super"),
+                            environment.get_at(distance - 1, "this"),
+                        )
+                    }
+                    _ => (None, None),
+                };
+                let (Some(Value::Class(superclass)), Some(this)) = (superclass, this) else {
+                    return Err(self.undefined_variable(keyword).into());
+                };
+                let method_name = method.identifier(self.source);
+                let method = superclass
+                    .find_method(&method_name)
+                    .ok_or_else(|| self.undefined_property(method))?;
+                Ok(Value::Function(Rc::new(method.bind(this))))
+            }
+            Expr::This { keyword } => self
+                .look_up_variable(keyword, "this")
+                .ok_or_else(|| self.undefined_variable(keyword).into()),
+            Expr::Variable { name } => self
+                .look_up_variable(name, &name.identifier(self.source))
+                .ok_or_else(|| self.undefined_variable(name).into()),
+            Expr::Unary { operator, right } => {
+                let right = self.evaluate(right)?;
+                match (operator.kind(), right) {
+                    (TokenKind::Minus, Value::Number(n)) => Ok(Value::Number(-n)),
+                    (TokenKind::Minus, _) => Err(Error::OperandMustBeNumber {
+                        span: operator.span().clone(),
+                    }
+                    .into()),
+                    (TokenKind::Bang, right) => Ok(Value::Bool(!right.is_truthy())),
+                    _ => unreachable!("invalid unary operator {operator}"),
+                }
+            }
+        })
+    }
+
+    /// Call `callee`, with errors reported at the call's closing `paren`.
+    fn call(
+        &mut self,
+        callee: Value<'src>,
+        paren: &Token,
+        arguments: Vec<Value<'src>>,
+    ) -> Result<Value<'src>, Unwind<'src>> {
+        let arity = match &callee {
+            Value::Function(function) => function.arity(),
+            Value::NativeFunction(native) => native.arity,
+            Value::Class(class) => class.arity(),
+            _ => {
+                return Err(Error::NotCallable {
+                    span: paren.span().clone(),
+                }
+                .into());
+            }
+        };
+        if arguments.len() != arity {
+            return Err(Error::ArityMismatch {
+                expected: arity,
+                found: arguments.len(),
+                span: paren.span().clone(),
+            }
+            .into());
+        }
+        if self.call_depth == MAX_CALL_DEPTH {
+            return Err(Error::StackOverflow {
+                span: paren.span().clone(),
+            }
+            .into());
+        }
+
+        match callee {
+            Value::Function(function) => self.call_function(&function, arguments),
+            Value::NativeFunction(native) => Ok((native.function)(&arguments)),
+            Value::Class(class) => {
+                let instance = Rc::new(RefCell::new(Instance::new(Rc::clone(&class))));
+                if let Some(init) = class.find_method("init") {
+                    let init = init.bind(Value::Instance(Rc::clone(&instance)));
+                    self.call_function(&init, arguments)?;
+                }
+                Ok(Value::Instance(instance))
+            }
+            _ => unreachable!("callee checked above"),
+        }
+    }
+
+    fn call_function(
+        &mut self,
+        function: &Function<'src>,
+        arguments: Vec<Value<'src>>,
+    ) -> Result<Value<'src>, Unwind<'src>> {
+        let mut environment = Environment::with_enclosing(Rc::clone(&function.closure));
+        for (param, argument) in function.declaration.params.iter().zip(arguments) {
+            environment.define(param.identifier(function.source).into_owned(), argument);
+        }
+
+        // The function may have been declared in a different source, e.g., an
+        // earlier REPL line.
+        let source = mem::replace(&mut self.source, function.source);
+        self.call_depth += 1;
+        let result = self.execute_block(&function.declaration.body, environment);
+        self.call_depth -= 1;
+        self.source = source;
+
+        match result {
+            // Initializers always return `this`, bound in their closure.
+            Ok(()) | Err(Unwind::Return(_)) if function.is_initializer => {
+                Ok(function.closure.borrow().get("this").unwrap_or(Value::Nil))
+            }
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(unwind) => Err(unwind),
+        }
+    }
+
+    /// Look up the variable `name`, referenced by `token`, in the scope the
+    /// resolver bound it to.
+    fn look_up_variable(&self, token: &Token, name: &str) -> Option<Value<'src>> {
+        match self.locals.get(&TokenId::from(token)) {
+            Some(&distance) => self.environment.borrow().get_at(distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn undefined_variable(&self, name: &Token) -> Error {
+        Error::UndefinedVariable {
+            name: name.lexeme(self.source).to_owned(),
+            span: name.span().clone(),
+        }
+    }
+
+    fn undefined_property(&self, name: &Token) -> Error {
+        Error::UndefinedProperty {
+            name: name.lexeme(self.source).to_owned(),
+            span: name.span().clone(),
+        }
+    }
+
+    /// Decode a literal token into its runtime value.
+    fn literal(&self, token: &Token) -> Value<'src> {
+        match token.kind() {
+            TokenKind::Keyword(Keyword::True) => Value::Bool(true),
+            TokenKind::Keyword(Keyword::False) => Value::Bool(false),
+            TokenKind::Keyword(Keyword::Nil) => Value::Nil,
+            TokenKind::Number => Value::Number(token.number_value(self.source)),
+            TokenKind::String
+            | TokenKind::StringHead
+            | TokenKind::StringMiddle
+            | TokenKind::StringTail => {
+                // The parser rejects invalid escapes before running.
+                let value = token
+                    .string_value(self.source)
+                    .expect("invalid string literal");
+                Value::String(Rc::from(value))
+            }
+            _ => unreachable!("invalid literal {token}"),
+        }
+    }
+}
+
+fn binary<'src>(
+    operator: &Token,
+    left: Value<'src>,
+    right: Value<'src>,
+) -> Result<Value<'src>, Error> {
+    let value = match (operator.kind(), left, right) {
+        (TokenKind::EqualEqual, left, right) => Value::Bool(left == right),
+        (TokenKind::BangEqual, left, right) => Value::Bool(left != right),
+        (TokenKind::Plus, Value::Number(l), Value::Number(r)) => Value::Number(l + r),
+        (TokenKind::Plus, Value::String(l), Value::String(r)) => {
+            Value::String(Rc::from(format!("{l}{r}")))
+        }
+        (TokenKind::Plus, _, _) => {
+            return Err(Error::OperandsMustBeNumbersOrStrings {
+                span: operator.span().clone(),
+            });
+        }
+        (kind, Value::Number(l), Value::Number(r)) => match kind {
+            TokenKind::Minus => Value::Number(l - r),
+            TokenKind::Star => Value::Number(l * r),
+            TokenKind::Slash => Value::Number(l / r),
+            TokenKind::Greater => Value::Bool(l > r),
+            TokenKind::GreaterEqual => Value::Bool(l >= r),
+            TokenKind::Less => Value::Bool(l < r),
+            TokenKind::LessEqual => Value::Bool(l <= r),
+            _ => unreachable!("invalid binary operator {operator}"),
+        },
+        _ => {
+            return Err(Error::OperandsMustBeNumbers {
+                span: operator.span().clone(),
+            });
+        }
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::source::{FileId, Span};
+    use crate::syntax;
+
+    use super::*;
+
+    /// Evaluate a single expression, given without its trailing `;`. The
+    /// source and syntax tree are leaked, since the value may borrow them.
+    fn evaluate(source: &str) -> Result<Value<'static>, Error> {
+        let source = format!("{source};").leak();
+        let syntax = syntax::parse(source);
+        let tokens = syntax.tokens().leak();
+        let statements = Parser::new(&syntax, tokens).parse().unwrap().leak();
+        let [Stmt::Expression { expression }] = &statements[..] else {
+            panic!("expected a single expression statement");
+        };
+        let mut interpreter = Interpreter::new();
+        interpreter.source = source;
+        match interpreter.evaluate(expression) {
+            Ok(value) => Ok(value),
+            Err(Unwind::Error(e)) => Err(e),
+            Err(_) => panic!("expression unwound without an error"),
+        }
+    }
+
+    /// Run a program and return what it printed.
+    fn run(source: &str) -> Result<String, Error> {
+        let syntax = syntax::parse(source);
+        let tokens = syntax.tokens();
+        let statements = Parser::new(&syntax, &tokens).parse().unwrap();
+        let locals = Resolver::new(source).resolve(&statements).unwrap();
+        let mut out = Vec::new();
+        let mut interpreter = Interpreter::with_output(&mut out);
+        interpreter.resolve(locals);
+        interpreter.interpret(source, &statements)?;
+        drop(interpreter);
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(evaluate("1 + 2 * 3"), Ok(Value::Number(7.0)));
+        assert_eq!(evaluate("(1 + 2) * 3"), Ok(Value::Number(9.0)));
+        assert_eq!(evaluate("-(4 / 8)"), Ok(Value::Number(-0.5)));
+    }
+
+    #[test]
+    fn comparison_and_equality() {
+        assert_eq!(evaluate("1 < 2 == true"), Ok(Value::Bool(true)));
+        assert_eq!(evaluate("2 >= 3"), Ok(Value::Bool(false)));
+        assert_eq!(evaluate("3 > 2"), Ok(Value::Bool(true)));
+        assert_eq!(evaluate("nil == false"), Ok(Value::Bool(false)));
+        assert_eq!(evaluate("\"a\" != \"a\""), Ok(Value::Bool(false)));
+        assert_eq!(evaluate("!nil"), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn string_concatenation() {
+        assert_eq!(
+            evaluate("\"Hello, \" + \"world!\""),
+            Ok(Value::String(Rc::from("Hello, world!")))
+        );
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(
+            evaluate(r#""tab\tnew\nline \u{1F600} \u{e9}\0""#),
+            Ok(Value::String(Rc::from("tab\tnew\nline \u{1F600} \u{e9}\0")))
+        );
+    }
+
+    #[test]
+    fn string_interpolation() {
+        assert_eq!(
+            run(r#"var name = "Lox"; print "Hi ${name}, ${1 + 1} ${nil} ${"x${true}"}!";"#),
+            Ok(String::from("Hi Lox, 2 nil xtrue!\n"))
+        );
+    }
+
+    #[test]
+    fn type_mismatch() {
+        let expected = Error::OperandsMustBeNumbers {
+            span: Span {
+                file: FileId(0),
+                start: 4,
+                end: 5,
+            },
+        };
+        assert_eq!(evaluate("\"a\" - 1"), Err(expected));
+        let expected = Error::OperandsMustBeNumbersOrStrings {
+            span: Span {
+                file: FileId(0),
+                start: 2,
+                end: 3,
+            },
+        };
+        assert_eq!(evaluate("1 + \"a\""), Err(expected));
+        let expected = Error::OperandMustBeNumber {
+            span: Span {
+                file: FileId(0),
+                start: 0,
+                end: 1,
+            },
+        };
+        assert_eq!(evaluate("-true"), Err(expected));
+    }
+
+    #[test]
+    fn print_statements() {
+        let source = "print 1 + 2; \"unused\"; print \"a\" + \"b\";";
+        assert_eq!(run(source), Ok(String::from("3\nab\n")));
+    }
+
+    /// Output whose reader has exited.
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn closed_output_stops_the_program() {
+        let source = "fun f() { while (true) print 1; } f();";
+        let syntax = syntax::parse(source);
+        let tokens = syntax.tokens();
+        let statements = Parser::new(&syntax, &tokens).parse().unwrap();
+        let locals = Resolver::new(source).resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::with_output(ClosedPipe);
+        interpreter.resolve(locals);
+        assert_eq!(interpreter.interpret(source, &statements), Ok(()));
+    }
+
+    #[test]
+    fn variables_and_scopes() {
+        let source = r#"
+            var a = "global a";
+            var b = "global b";
+            {
+                var a = "outer a";
+                {
+                    b = a;
+                    var a = 1;
+                    a = a + 1;
+                    print a;
+                }
+                print a;
+            }
+            print a;
+            print b;
+            var c;
+            print c;
+        "#;
+        let expected = "2\nouter a\nglobal a\nouter a\nnil\n";
+        assert_eq!(run(source), Ok(String::from(expected)));
+    }
+
+    #[test]
+    fn unicode_identifiers_are_normalized() {
+        // Precomposed and decomposed spellings of the same name.
+        assert_eq!(
+            run("var caf\u{e9} = 1; { var größe = cafe\u{301} + 1; print größe; }"),
+            Ok(String::from("2\n"))
+        );
+    }
+
+    #[test]
+    fn undefined_variable() {
+        let expected = Error::UndefinedVariable {
+            name: String::from("b"),
+            span: Span {
+                file: FileId(0),
+                start: 19,
+                end: 20,
+            },
+        };
+        assert_eq!(run("var a = 1; { print b; }"), Err(expected));
+        let expected = Error::UndefinedVariable {
+            name: String::from("c"),
+            span: Span {
+                file: FileId(0),
+                start: 2,
+                end: 3,
+            },
+        };
+        assert_eq!(run("{ c = 1; }"), Err(expected));
+    }
+
+    #[test]
+    fn control_flow() {
+        let source = r#"
+            var a = 0;
+            var b = 1;
+            while (a < 50) {
+                print a;
+                var temp = a;
+                a = b;
+                b = temp + b;
+            }
+            for (var i = 0; i < 3; i = i + 1) {
+                if (i == 1) print "one"; else print i;
+            }
+        "#;
+        let expected = "0\n1\n1\n2\n3\n5\n8\n13\n21\n34\n0\none\n2\n";
+        assert_eq!(run(source), Ok(String::from(expected)));
+    }
+
+    #[test]
+    fn logical_short_circuit() {
+        assert_eq!(
+            evaluate("\"hi\" or undefined"),
+            Ok(Value::String(Rc::from("hi")))
+        );
+        assert_eq!(evaluate("nil and undefined"), Ok(Value::Nil));
+        assert_eq!(evaluate("nil or false"), Ok(Value::Bool(false)));
+        assert_eq!(evaluate("1 and 2"), Ok(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn functions_and_closures() {
+        let source = r#"
+            fun fib(n) {
+                if (n < 2) return n;
+                return fib(n - 1) + fib(n - 2);
+            }
+            print fib(10);
+
+            fun makeCounter() {
+                var i = 0;
+                fun count() {
+                    i = i + 1;
+                    return i;
+                }
+                return count;
+            }
+            var counter = makeCounter();
+            counter();
+            print counter();
+
+            fun find(limit) {
+                for (var i = 0; ; i = i + 1) {
+                    while (true) {
+                        if (i * i > limit) return i;
+                        i = i + 1;
+                    }
+                }
+            }
+            print find(10);
+            print fib;
+            print clock() > 0;
+        "#;
+        let expected = "55\n2\n4\n<fn fib>\ntrue\n";
+        assert_eq!(run(source), Ok(String::from(expected)));
+    }
+
+    #[test]
+    fn call_errors() {
+        let expected = Error::ArityMismatch {
+            expected: 2,
+            found: 1,
+            span: Span {
+                file: FileId(0),
+                start: 24,
+                end: 25,
+            },
+        };
+        assert_eq!(run("fun f(a, b) {} print f(1);"), Err(expected));
+        let expected = Error::NotCallable {
+            span: Span {
+                file: FileId(0),
+                start: 14,
+                end: 15,
+            },
+        };
+        assert_eq!(run("var a = 1; a(2);"), Err(expected));
+    }
+
+    #[test]
+    fn unbounded_recursion() {
+        let expected = Error::StackOverflow {
+            span: Span {
+                file: FileId(0),
+                start: 12,
+                end: 13,
+            },
+        };
+        assert_eq!(run("fun f() { f(); } f();"), Err(expected));
+        let source = "class A { init() { A(); } } A();";
+        assert!(matches!(run(source), Err(Error::StackOverflow { .. })));
+        // Calls just under the limit still work.
+        let source = r#"
+            fun count(n) {
+              if (n == 0) return 0;
+              return 1 + count(n - 1);
+            }
+            print count(2000);
+        "#;
+        assert_eq!(run(source), Ok(String::from("2000\n")));
+    }
+
+    #[test]
+    fn classes() {
+        let source = r#"
+            class Shape {
+                init(name) {
+                    this.name = name;
+                }
+                describe() {
+                    return "a " + this.name;
+                }
+                area() {
+                    return 0;
+                }
+            }
+            class Square < Shape {
+                init(side) {
+                    super.init("square");
+                    this.side = side;
+                    return;
+                }
+                area() {
+                    return this.side * this.side;
+                }
+            }
+            var square = Square(3);
+            print square.describe;
+            print square.describe() == "a square";
+            print square.init(2) == square;
+            print square.area();
+            var method = square.area;
+            square.side = 4;
+            print method();
+            print Square;
+            print square;
+        "#;
+        let expected = "<fn describe>\ntrue\ntrue\n4\n16\nSquare\nSquare instance\n";
+        assert_eq!(run(source), Ok(String::from(expected)));
+    }
+
+    #[test]
+    fn class_errors() {
+        let expected = Error::SuperclassMustBeClass {
+            span: Span {
+                file: FileId(0),
+                start: 21,
+                end: 22,
+            },
+        };
+        assert_eq!(run("var A = 1; class B < A {}"), Err(expected));
+        let expected = Error::UndefinedProperty {
+            name: String::from("missing"),
+            span: Span {
+                file: FileId(0),
+                start: 15,
+                end: 22,
+            },
+        };
+        assert_eq!(run("class A {} A().missing;"), Err(expected));
+        let expected = Error::OnlyInstancesHaveProperties {
+            span: Span {
+                file: FileId(0),
+                start: 4,
+                end: 5,
+            },
+        };
+        assert_eq!(run("\"a\".b;"), Err(expected));
+        let expected = Error::OnlyInstancesHaveFields {
+            span: Span {
+                file: FileId(0),
+                start: 2,
+                end: 3,
+            },
+        };
+        assert_eq!(run("1.b = 2;"), Err(expected));
+    }
+
+    #[test]
+    fn closures_capture_resolved_scope() {
+        let source = r#"
+            var a = "global";
+            {
+                fun showA() {
+                    print a;
+                }
+                showA();
+                var a = "block";
+                showA();
+                print a;
+            }
+        "#;
+        let expected = "global\nglobal\nblock\n";
+        assert_eq!(run(source), Ok(String::from(expected)));
+    }
+}
@@ -1,6 +1,20 @@
+pub mod class;
+pub mod diagnostic;
+pub mod emit;
+pub mod environment;
 pub mod error;
+pub mod error_codes;
 pub mod expr;
+pub mod format;
+pub mod function;
+pub mod interpreter;
+pub mod lsp;
 pub mod parser;
+pub mod resolver;
 pub mod scanner;
 pub mod source;
+mod stack;
+pub mod stmt;
+pub mod syntax;
 pub mod token;
+pub mod value;
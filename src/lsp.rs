@@ -0,0 +1,1046 @@
+//! A Language Server Protocol server, for editors to show errors, highlight,
+//! outline and navigate Lox code as it's written.
+//!
+//! The server talks JSON-RPC over any reader and writer, normally stdin and
+//! stdout. Documents are kept in sync as the editor changes them, and
+//! requests are answered from the latest text, parsed and resolved once for
+//! each version of it. The parser recovers from errors, so answers cover
+//! code around them too.
+//! Positions are lines and UTF-16 columns, as the protocol counts them.
+
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::ops::ControlFlow;
+use std::slice;
+
+use camino::Utf8PathBuf;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+use crate::diagnostic::{self, Severity};
+use crate::error::Error;
+use crate::parser::Parser;
+use crate::resolver::{Resolver, TokenId};
+use crate::source::{Edit, FileId, Source, Span};
+use crate::stmt::{FunctionDecl, Stmt};
+use crate::syntax::ast::{self, AstNode};
+use crate::syntax::{self, Parse, SyntaxNode, SyntaxToken, Trivia};
+use crate::token::{Token, TokenKind};
+
+mod protocol;
+mod rpc;
+
+use protocol::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentSymbol, Hover, Location,
+    MarkupContent, Position, PublishDiagnosticsParams, Range, SemanticTokens, SymbolKind,
+    TextDocumentParams, TextDocumentPositionParams,
+};
+use rpc::{Message, Notification, Response, ResponseError};
+
+/// The semantic token types, indexed by the types in [`SemanticTokens`].
+const TOKEN_TYPES: &[&str] = &[
+    "keyword", "variable", "string", "number", "comment", "operator",
+];
+
+/// Returns the index of the semantic token type of `kind` in
+/// [`TOKEN_TYPES`], or `None` if it isn't highlighted, like punctuation.
+fn token_type(kind: TokenKind) -> Option<u32> {
+    let name = match kind {
+        TokenKind::Keyword(_) => "keyword",
+        TokenKind::Identifier => "variable",
+        TokenKind::String
+        | TokenKind::StringHead
+        | TokenKind::StringMiddle
+        | TokenKind::StringTail
+        | TokenKind::UnterminatedString => "string",
+        TokenKind::Number | TokenKind::InvalidNumber => "number",
+        TokenKind::LineComment | TokenKind::BlockComment | TokenKind::UnterminatedBlockComment => {
+            "comment"
+        }
+        TokenKind::Minus
+        | TokenKind::Plus
+        | TokenKind::Slash
+        | TokenKind::Star
+        | TokenKind::Bang
+        | TokenKind::BangEqual
+        | TokenKind::Equal
+        | TokenKind::EqualEqual
+        | TokenKind::Greater
+        | TokenKind::GreaterEqual
+        | TokenKind::Less
+        | TokenKind::LessEqual => "operator",
+        TokenKind::Whitespace
+        | TokenKind::LeftParen
+        | TokenKind::RightParen
+        | TokenKind::LeftBrace
+        | TokenKind::RightBrace
+        | TokenKind::Comma
+        | TokenKind::Dot
+        | TokenKind::Semicolon
+        | TokenKind::InvalidCharacter
+        | TokenKind::Eof => return None,
+    };
+    TOKEN_TYPES
+        .iter()
+        .position(|&token_type| token_type == name)
+        .map(|index| index as u32)
+}
+
+/// Serve the Language Server Protocol, reading messages from `input` and
+/// writing them to `output`, until the client sends `exit` or closes the
+/// input. Returns whether the client asked the server to shut down first,
+/// which makes the exit a clean one.
+pub fn serve(mut input: impl BufRead, output: impl Write) -> io::Result<bool> {
+    let mut server = Server::new(output);
+    while let Some(body) = rpc::read(&mut input)? {
+        if server.handle(&body)?.is_break() {
+            break;
+        }
+    }
+    Ok(server.state == State::ShutDown)
+}
+
+/// Where the server is in the lifecycle the client drives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// Waiting for the `initialize` request.
+    Uninitialized,
+    Running,
+    /// After the `shutdown` request, waiting for the `exit` notification.
+    ShutDown,
+}
+
+struct Server<W> {
+    output: W,
+    state: State,
+    /// Open documents, by URI.
+    documents: HashMap<String, Document>,
+}
+
+impl<W: Write> Server<W> {
+    fn new(output: W) -> Self {
+        Self {
+            output,
+            state: State::Uninitialized,
+            documents: HashMap::new(),
+        }
+    }
+
+    /// Handle a message, breaking once the client sends `exit`.
+    fn handle(&mut self, body: &str) -> io::Result<ControlFlow<()>> {
+        let message: Message = match serde_json::from_str(body) {
+            Ok(message) => message,
+            Err(e) => {
+                let error = ResponseError::new(rpc::PARSE_ERROR, e.to_string());
+                rpc::write(&mut self.output, &Response::new(Value::Null, Err(error)))?;
+                return Ok(ControlFlow::Continue(()));
+            }
+        };
+        let Some(method) = message.method else {
+            return Ok(ControlFlow::Continue(()));
+        };
+        match message.id {
+            Some(id) => {
+                let result = self.request(&method, message.params);
+                rpc::write(&mut self.output, &Response::new(id, result))?;
+            }
+            None if method == "exit" => return Ok(ControlFlow::Break(())),
+            None => self.notification(&method, message.params)?,
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Result<Value, ResponseError> {
+        match (self.state, method) {
+            (State::Uninitialized, "initialize") => {
+                self.state = State::Running;
+                Ok(capabilities())
+            }
+            (State::Uninitialized, _) => Err(ResponseError::new(
+                rpc::SERVER_NOT_INITIALIZED,
+                "the server isn't initialized",
+            )),
+            (State::ShutDown, _) => Err(ResponseError::new(
+                rpc::INVALID_REQUEST,
+                "the server is shut down",
+            )),
+            (State::Running, "initialize") => Err(ResponseError::new(
+                rpc::INVALID_REQUEST,
+                "the server is already initialized",
+            )),
+            (State::Running, "shutdown") => {
+                self.state = State::ShutDown;
+                Ok(Value::Null)
+            }
+            (State::Running, "textDocument/semanticTokens/full") => {
+                let params: TextDocumentParams = parse_params(params)?;
+                let document = self.document(&params.text_document.uri)?;
+                Ok(to_json(document.semantic_tokens()))
+            }
+            (State::Running, "textDocument/documentSymbol") => {
+                let params: TextDocumentParams = parse_params(params)?;
+                let document = self.document(&params.text_document.uri)?;
+                Ok(to_json(document.symbols()))
+            }
+            (State::Running, "textDocument/definition") => {
+                let params: TextDocumentPositionParams = parse_params(params)?;
+                let uri = params.text_document.uri;
+                let document = self.document(&uri)?;
+                let location = document
+                    .declaration(params.position)
+                    .map(|(_, declaration)| Location {
+                        uri: uri.clone(),
+                        range: document.range(&declaration.span),
+                    });
+                Ok(to_json(location))
+            }
+            (State::Running, "textDocument/hover") => {
+                let params: TextDocumentPositionParams = parse_params(params)?;
+                let document = self.document(&params.text_document.uri)?;
+                Ok(to_json(document.hover(params.position)))
+            }
+            (State::Running, _) => Err(ResponseError::new(
+                rpc::METHOD_NOT_FOUND,
+                format!("unknown method {method}"),
+            )),
+        }
+    }
+
+    /// Handle a notification. Ones the server doesn't know, or that come
+    /// before it's running, are ignored, as there's no way to reply.
+    fn notification(&mut self, method: &str, params: Value) -> io::Result<()> {
+        if self.state != State::Running {
+            return Ok(());
+        }
+        match method {
+            "textDocument/didOpen" => {
+                let Ok(params) = parse_params::<DidOpenTextDocumentParams>(params) else {
+                    return Ok(());
+                };
+                let item = params.text_document;
+                let document = Document::new(&item.uri, &item.text, item.version);
+                self.documents.insert(item.uri.clone(), document);
+                self.publish_diagnostics(&item.uri)
+            }
+            "textDocument/didChange" => {
+                let Ok(params) = parse_params::<DidChangeTextDocumentParams>(params) else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                let Some(document) = self.documents.get_mut(&uri) else {
+                    return Ok(());
+                };
+                for change in params.content_changes {
+                    match change.range {
+                        Some(range) => document.edit(range, change.text),
+                        None => *document = Document::new(&uri, &change.text, 0),
+                    }
+                }
+                document.version = params.text_document.version;
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didClose" => {
+                let Ok(params) = parse_params::<DidCloseTextDocumentParams>(params) else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn document(&self, uri: &str) -> Result<&Document, ResponseError> {
+        self.documents.get(uri).ok_or_else(|| {
+            ResponseError::new(rpc::INVALID_PARAMS, format!("document {uri} isn't open"))
+        })
+    }
+
+    /// Send the diagnostics of the document at `uri`, or clear them if it's
+    /// closed.
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let params = match self.documents.get(uri) {
+            Some(document) => PublishDiagnosticsParams {
+                uri: uri.to_owned(),
+                version: Some(document.version),
+                diagnostics: document.diagnostics(uri),
+            },
+            None => PublishDiagnosticsParams {
+                uri: uri.to_owned(),
+                version: None,
+                diagnostics: Vec::new(),
+            },
+        };
+        let notification = Notification::new("textDocument/publishDiagnostics", params);
+        rpc::write(&mut self.output, &notification)
+    }
+}
+
+/// What the server can do, in reply to `initialize`.
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            "positionEncoding": "utf-16",
+            // Open and close notifications, and changes as edits.
+            "textDocumentSync": { "openClose": true, "change": 2 },
+            "semanticTokensProvider": {
+                "legend": { "tokenTypes": TOKEN_TYPES, "tokenModifiers": [] },
+                "full": true,
+            },
+            "documentSymbolProvider": true,
+            "definitionProvider": true,
+            "hoverProvider": true,
+        },
+        "serverInfo": { "name": "lox", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn parse_params<P: DeserializeOwned>(params: Value) -> Result<P, ResponseError> {
+    serde_json::from_value(params)
+        .map_err(|e| ResponseError::new(rpc::INVALID_PARAMS, e.to_string()))
+}
+
+fn to_json(value: impl Serialize) -> Value {
+    serde_json::to_value(value).expect("protocol types serialize to JSON")
+}
+
+/// An open document, as the editor last sent it.
+struct Document {
+    source: Source,
+    version: i32,
+    /// The lossless syntax tree, reparsed as the document is edited, for
+    /// the ranges of whole declarations.
+    syntax: Parse,
+    /// Worked out on the first request that needs them, and dropped when
+    /// the text changes.
+    analysis: OnceCell<Analysis>,
+    semantic_tokens: OnceCell<SemanticTokens>,
+}
+
+/// A version of a document, lowered and resolved.
+struct Analysis {
+    /// The syntax errors, or if there are none, the semantic errors.
+    errors: Vec<Error>,
+    /// The tokens, without trivia, to find the one at a position.
+    tokens: Vec<Token>,
+    /// The declaration of each variable, by the index in `tokens` of each
+    /// token naming it.
+    declarations: HashMap<usize, Declaration>,
+}
+
+struct Declaration {
+    span: Span,
+    /// Like `fun add(a, b)`.
+    description: String,
+}
+
+impl Analysis {
+    /// Like the interpreter, semantic errors aren't kept for code that
+    /// doesn't parse, since a part of the tree missing can cause them.
+    fn new(syntax: &Parse, source: &str) -> Self {
+        let tokens = syntax.tokens();
+        let (statements, mut errors) = Parser::new(syntax, &tokens).parse_partial();
+        let (definitions, semantic_errors) = Resolver::new(source).resolve_partial(&statements);
+        if errors.is_empty() {
+            errors = semantic_errors;
+        }
+        let mut descriptions = HashMap::new();
+        describe(&statements, source, &mut descriptions);
+        let declarations = tokens
+            .iter()
+            .enumerate()
+            .filter_map(|(index, token)| {
+                let &declaration = definitions.get(&TokenId::from(token))?;
+                let description = descriptions
+                    .get(&TokenId::from(declaration))
+                    .cloned()
+                    .unwrap_or_else(|| declaration.lexeme(source).to_owned());
+                let span = declaration.span().clone();
+                Some((index, Declaration { span, description }))
+            })
+            .collect();
+        Self {
+            errors,
+            tokens,
+            declarations,
+        }
+    }
+}
+
+impl Document {
+    fn new(uri: &str, text: &str, version: i32) -> Self {
+        Self {
+            source: Source::new(FileId::default(), Utf8PathBuf::from(uri), Box::from(text)),
+            version,
+            syntax: syntax::parse(text),
+            analysis: OnceCell::new(),
+            semantic_tokens: OnceCell::new(),
+        }
+    }
+
+    /// Replace the text in `range` with `text`.
+    fn edit(&mut self, range: Range, text: String) {
+        let start = self.offset(range.start);
+        let end = self.offset(range.end).max(start);
+        let edit = Edit::new(start..end, text);
+        self.source.edit(&edit);
+        self.syntax = self.syntax.reparse(self.source.as_str(), &edit);
+        self.analysis.take();
+        self.semantic_tokens.take();
+    }
+
+    fn analysis(&self) -> &Analysis {
+        self.analysis
+            .get_or_init(|| Analysis::new(&self.syntax, self.source.as_str()))
+    }
+
+    fn offset(&self, position: Position) -> usize {
+        self.source
+            .utf16_offset(position.line as usize, position.character as usize)
+    }
+
+    fn range(&self, span: &Span) -> Range {
+        self.source.span_to_range(span).into()
+    }
+
+    /// Returns the syntax errors, or if there are none, the semantic errors.
+    fn diagnostics(&self, uri: &str) -> Vec<protocol::Diagnostic> {
+        self.analysis()
+            .errors
+            .iter()
+            .map(|error| self.diagnostic(uri, &error.diagnostic()))
+            .collect()
+    }
+
+    fn diagnostic(&self, uri: &str, diagnostic: &diagnostic::Diagnostic) -> protocol::Diagnostic {
+        let mut message = diagnostic.message.clone();
+        for note in &diagnostic.notes {
+            message.push_str(&format!("\nnote: {note}"));
+        }
+        if let Some(help) = &diagnostic.help {
+            message.push_str(&format!("\nhelp: {help}"));
+        }
+        let related_information = diagnostic
+            .labels
+            .iter()
+            .filter(|label| !label.primary)
+            .map(|label| DiagnosticRelatedInformation {
+                location: Location {
+                    uri: uri.to_owned(),
+                    range: self.range(&label.span),
+                },
+                message: label.message.clone().unwrap_or_default(),
+            })
+            .collect();
+        let span = diagnostic
+            .primary_span()
+            .expect("errors have a primary label");
+        protocol::Diagnostic {
+            range: self.range(span),
+            severity: match diagnostic.severity {
+                Severity::Error => DiagnosticSeverity::Error,
+                Severity::Warning => DiagnosticSeverity::Warning,
+            },
+            code: diagnostic.code,
+            source: "lox",
+            message,
+            related_information,
+        }
+    }
+
+    fn semantic_tokens(&self) -> &SemanticTokens {
+        self.semantic_tokens.get_or_init(|| self.highlight())
+    }
+
+    fn highlight(&self) -> SemanticTokens {
+        // The tokens of the tree, with the comments in their trivia.
+        let mut spans = Vec::new();
+        for token in self.syntax.syntax().tokens() {
+            let span = token.span();
+            trivia_spans(&mut spans, token.leading(), token.full_span());
+            spans.push((token.kind(), span.clone()));
+            trivia_spans(
+                &mut spans,
+                token.trailing(),
+                Span {
+                    start: span.end,
+                    ..span
+                },
+            );
+        }
+
+        let mut data = Vec::new();
+        let (mut previous_line, mut previous_start) = (0, 0);
+        for (kind, span) in spans {
+            let Some(token_type) = token_type(kind) else {
+                continue;
+            };
+            // Not every editor takes tokens spanning lines, like block
+            // comments, so they're split into one for each line.
+            let range = self.source.span_to_range(&span);
+            for line in range.start.line..=range.end.line {
+                let start = if line == range.start.line {
+                    range.start.utf16
+                } else {
+                    0
+                };
+                let end = if line == range.end.line {
+                    range.end.utf16
+                } else {
+                    let text = self.source.line_text(line as usize).unwrap_or_default();
+                    text.encode_utf16().count() as u32
+                };
+                if start == end {
+                    continue;
+                }
+                let delta_start = if line == previous_line {
+                    start - previous_start
+                } else {
+                    start
+                };
+                data.extend([
+                    line - previous_line,
+                    delta_start,
+                    end - start,
+                    token_type,
+                    0,
+                ]);
+                (previous_line, previous_start) = (line, start);
+            }
+        }
+        SemanticTokens { data }
+    }
+
+    /// Returns the classes and functions declared in the document, with the
+    /// methods of classes and the functions inside functions nested in them.
+    fn symbols(&self) -> Vec<DocumentSymbol> {
+        self.symbols_in(&self.syntax.syntax())
+    }
+
+    fn symbols_in(&self, node: &SyntaxNode) -> Vec<DocumentSymbol> {
+        let mut symbols = Vec::new();
+        for child in node.children() {
+            if let Some(class) = ast::ClassDecl::cast(child.clone())
+                && let Some(name) = class.name()
+            {
+                let methods = class
+                    .methods()
+                    .filter_map(|method| self.function_symbol(&method, SymbolKind::Method))
+                    .collect();
+                let detail = class
+                    .superclass()
+                    .map(|superclass| format!("< {}", superclass.text()));
+                symbols.push(self.symbol(&name, detail, SymbolKind::Class, &child, methods));
+            } else if let Some(function) =
+                ast::FunDecl::cast(child.clone()).and_then(|declaration| declaration.function())
+                && let Some(mut symbol) = self.function_symbol(&function, SymbolKind::Function)
+            {
+                // Include the `fun` keyword.
+                symbol.range = self.range(&child.span());
+                symbols.push(symbol);
+            } else {
+                symbols.extend(self.symbols_in(&child));
+            }
+        }
+        symbols
+    }
+
+    fn function_symbol(
+        &self,
+        function: &ast::Function,
+        kind: SymbolKind,
+    ) -> Option<DocumentSymbol> {
+        let name = function.name()?;
+        let params = function.params().map(|params| {
+            let names: Vec<_> = params
+                .params()
+                .map(|param| param.text().to_owned())
+                .collect();
+            format!("({})", names.join(", "))
+        });
+        let children = self.symbols_in(function.syntax());
+        Some(self.symbol(&name, params, kind, function.syntax(), children))
+    }
+
+    fn symbol(
+        &self,
+        name: &SyntaxToken,
+        detail: Option<String>,
+        kind: SymbolKind,
+        node: &SyntaxNode,
+        children: Vec<DocumentSymbol>,
+    ) -> DocumentSymbol {
+        DocumentSymbol {
+            name: name.text().to_owned(),
+            detail,
+            kind,
+            range: self.range(&node.span()),
+            selection_range: self.range(&name.span()),
+            children,
+        }
+    }
+
+    /// Returns the identifier at `position` and its declaration, if it's a
+    /// variable that's declared. A position just after an identifier counts
+    /// as on it, as editors put the cursor between chars.
+    fn declaration(&self, position: Position) -> Option<(&Token, &Declaration)> {
+        let analysis = self.analysis();
+        let offset = self.offset(position) as u32;
+        let first = analysis
+            .tokens
+            .partition_point(|token| token.span().end < offset);
+        let (index, token) = analysis.tokens[first..]
+            .iter()
+            .take_while(|token| token.span().start <= offset)
+            .enumerate()
+            .find(|(_, token)| token.kind() == TokenKind::Identifier)?;
+        let declaration = analysis.declarations.get(&(first + index))?;
+        Some((token, declaration))
+    }
+
+    /// Describe the variable at `position` by its declaration.
+    fn hover(&self, position: Position) -> Option<Hover> {
+        let (token, declaration) = self.declaration(position)?;
+        Some(Hover {
+            contents: MarkupContent {
+                kind: "markdown",
+                value: format!("```lox\n{}\n```", declaration.description),
+            },
+            range: self.range(token.span()),
+        })
+    }
+}
+
+/// Push the kind and span of each of `trivia`, the first starting where
+/// `span` does.
+fn trivia_spans(spans: &mut Vec<(TokenKind, Span)>, trivia: &[Trivia], span: Span) {
+    let mut start = span.start;
+    for trivia in trivia {
+        let end = start + trivia.text().len() as u32;
+        spans.push((trivia.kind(), Span { start, end, ..span }));
+        start = end;
+    }
+}
+
+/// Describe each variable declared in `statements`, like `fun add(a, b)`,
+/// by the token declaring it.
+fn describe(statements: &[Stmt], source: &str, descriptions: &mut HashMap<TokenId, String>) {
+    for statement in statements {
+        match statement {
+            Stmt::Block { statements } => describe(statements, source, descriptions),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let description = match superclass.as_deref() {
+                    Some(superclass) => format!("class {} < {superclass}", name.lexeme(source)),
+                    None => format!("class {}", name.lexeme(source)),
+                };
+                descriptions.insert(TokenId::from(*name), description);
+                for method in methods {
+                    describe_function(method, source, descriptions);
+                }
+            }
+            Stmt::Function { declaration } => describe_function(declaration, source, descriptions),
+            Stmt::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                describe(slice::from_ref(then_branch), source, descriptions);
+                if let Some(else_branch) = else_branch.as_deref() {
+                    describe(slice::from_ref(else_branch), source, descriptions);
+                }
+            }
+            Stmt::Var { name, .. } => {
+                descriptions.insert(TokenId::from(*name), format!("var {}", name.lexeme(source)));
+            }
+            Stmt::While { body, .. } => describe(slice::from_ref(body), source, descriptions),
+            _ => {}
+        }
+    }
+}
+
+fn describe_function(
+    function: &FunctionDecl,
+    source: &str,
+    descriptions: &mut HashMap<TokenId, String>,
+) {
+    let params: Vec<_> = function
+        .params
+        .iter()
+        .map(|param| param.lexeme(source))
+        .collect();
+    let description = format!(
+        "fun {}({})",
+        function.name.lexeme(source),
+        params.join(", ")
+    );
+    descriptions.insert(TokenId::from(function.name), description);
+    for param in &function.params {
+        descriptions.insert(
+            TokenId::from(*param),
+            format!("(parameter) {}", param.lexeme(source)),
+        );
+    }
+    describe(&function.body, source, descriptions);
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    const URI: &str = "file:///test.lox";
+
+    fn request(id: u32, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn notification(method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "method": method, "params": params })
+    }
+
+    fn open(text: &str) -> Value {
+        notification(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": URI, "languageId": "lox", "version": 1, "text": text },
+            }),
+        )
+    }
+
+    fn at(line: u32, character: u32) -> Value {
+        json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+    }
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Value {
+        json!({
+            "start": { "line": start.0, "character": start.1 },
+            "end": { "line": end.0, "character": end.1 },
+        })
+    }
+
+    /// Run the server with a client sending `messages`, then closing the
+    /// input. Returns what the server sent, and whether it exited cleanly.
+    fn exchange(messages: &[Value]) -> (Vec<Value>, bool) {
+        let mut input = Vec::new();
+        for message in messages {
+            rpc::write(&mut input, message).unwrap();
+        }
+        let mut output = Vec::new();
+        let clean = serve(&input[..], &mut output).unwrap();
+        let mut output = &output[..];
+        let mut replies = Vec::new();
+        while let Some(body) = rpc::read(&mut output).unwrap() {
+            replies.push(serde_json::from_str(&body).unwrap());
+        }
+        (replies, clean)
+    }
+
+    /// Initialize the server, then send `messages`, and return what the
+    /// server sent after initializing.
+    fn session(messages: &[Value]) -> Vec<Value> {
+        let mut all = vec![request(0, "initialize", json!({ "capabilities": {} }))];
+        all.extend_from_slice(messages);
+        let (mut replies, _) = exchange(&all);
+        replies.remove(0);
+        replies
+    }
+
+    #[test]
+    fn lifecycle() {
+        let (replies, clean) = exchange(&[
+            request(1, "textDocument/hover", at(0, 0)),
+            request(2, "initialize", json!({ "capabilities": {} })),
+            notification("initialized", json!({})),
+            request(3, "textDocument/rename", json!({})),
+            json!({ "jsonrpc": "2.0", "id": 4, "result": null }),
+            request(5, "shutdown", Value::Null),
+            request(6, "textDocument/hover", at(0, 0)),
+            notification("exit", Value::Null),
+            request(7, "shutdown", Value::Null),
+        ]);
+        assert!(clean);
+        let codes: Vec<_> = replies
+            .iter()
+            .map(|reply| (reply["id"].clone(), reply["error"]["code"].clone()))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                (json!(1), json!(rpc::SERVER_NOT_INITIALIZED)),
+                (json!(2), Value::Null),
+                (json!(3), json!(rpc::METHOD_NOT_FOUND)),
+                (json!(5), Value::Null),
+                (json!(6), json!(rpc::INVALID_REQUEST)),
+            ]
+        );
+        assert_eq!(replies[1]["result"], capabilities());
+        assert_eq!(replies[3]["result"], Value::Null);
+
+        // Exiting without shutting down, and input that isn't JSON.
+        let mut input = Vec::new();
+        input.extend_from_slice(b"Content-Length: 5\r\n\r\nhello");
+        rpc::write(&mut input, &notification("exit", Value::Null)).unwrap();
+        let mut output = Vec::new();
+        assert!(!serve(&input[..], &mut output).unwrap());
+        let body = rpc::read(&mut &output[..]).unwrap().unwrap();
+        let reply: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(reply["id"], Value::Null);
+        assert_eq!(reply["error"]["code"], rpc::PARSE_ERROR);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let replies = session(&[
+            // `😀` is 2 UTF-16 code units, and 4 bytes.
+            open("print \"😀\" + ;\nprint (1;\n"),
+            notification(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": { "uri": URI, "version": 2 },
+                    "contentChanges": [
+                        { "range": range((0, 13), (0, 13)), "text": "1" },
+                        { "range": range((1, 8), (1, 8)), "text": ")" },
+                    ],
+                }),
+            ),
+            notification(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": { "uri": URI, "version": 3 },
+                    "contentChanges": [{ "text": "return 1;" }],
+                }),
+            ),
+            notification(
+                "textDocument/didClose",
+                json!({ "textDocument": { "uri": URI } }),
+            ),
+        ]);
+        let published: Vec<_> = replies
+            .iter()
+            .map(|reply| {
+                assert_eq!(reply["method"], "textDocument/publishDiagnostics");
+                assert_eq!(reply["params"]["uri"], URI);
+                (
+                    reply["params"]["version"].clone(),
+                    reply["params"]["diagnostics"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            published,
+            [
+                (
+                    json!(1),
+                    json!([
+                        {
+                            "range": range((0, 13), (0, 14)),
+                            "severity": 1,
+                            "code": "E0005",
+                            "source": "lox",
+                            "message": "parse error",
+                        },
+                        {
+                            "range": range((1, 6), (1, 7)),
+                            "severity": 1,
+                            "code": "E0004",
+                            "source": "lox",
+                            "message": "unclosed parenthesis",
+                            "relatedInformation": [{
+                                "location": { "uri": URI, "range": range((1, 8), (1, 9)) },
+                                "message": "expected ')' here",
+                            }],
+                        },
+                    ])
+                ),
+                (json!(2), json!([])),
+                // Semantic errors once the code parses.
+                (
+                    json!(3),
+                    json!([{
+                        "range": range((0, 0), (0, 6)),
+                        "severity": 1,
+                        "code": "E0016",
+                        "source": "lox",
+                        "message": "can't return from top-level code",
+                    }])
+                ),
+                (Value::Null, json!([])),
+            ]
+        );
+    }
+
+    #[test]
+    fn semantic_tokens() {
+        let replies = session(&[
+            open("var s = \"é\"; /* two\nlines */ print -s;"),
+            request(
+                1,
+                "textDocument/semanticTokens/full",
+                json!({ "textDocument": { "uri": URI } }),
+            ),
+        ]);
+        let data: Vec<u32> = serde_json::from_value(replies[1]["result"]["data"].clone()).unwrap();
+        let tokens: Vec<_> = data.chunks(5).collect();
+        let expected = [
+            [0, 0, 3, 0, 0], // var
+            [0, 4, 1, 1, 0], // s
+            [0, 2, 1, 5, 0], // =
+            [0, 2, 3, 2, 0], // "é"
+            [0, 5, 6, 4, 0], // /* two
+            [1, 0, 8, 4, 0], // lines */
+            [0, 9, 5, 0, 0], // print
+            [0, 6, 1, 5, 0], // -
+            [0, 1, 1, 1, 0], // s
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn document_symbols() {
+        let source = indoc! {"
+            class Square < Shape {
+              area() { return 1; }
+            }
+            if (true) {
+              fun outer(a, b) {
+                fun inner() {}
+              }
+            }
+        "};
+        let replies = session(&[
+            open(source),
+            request(
+                1,
+                "textDocument/documentSymbol",
+                json!({ "textDocument": { "uri": URI } }),
+            ),
+        ]);
+        let expected = json!([
+            {
+                "name": "Square",
+                "detail": "< Shape",
+                "kind": 5,
+                "range": range((0, 0), (2, 1)),
+                "selectionRange": range((0, 6), (0, 12)),
+                "children": [{
+                    "name": "area",
+                    "detail": "()",
+                    "kind": 6,
+                    "range": range((1, 2), (1, 22)),
+                    "selectionRange": range((1, 2), (1, 6)),
+                    "children": [],
+                }],
+            },
+            {
+                "name": "outer",
+                "detail": "(a, b)",
+                "kind": 12,
+                "range": range((4, 2), (6, 3)),
+                "selectionRange": range((4, 6), (4, 11)),
+                "children": [{
+                    "name": "inner",
+                    "detail": "()",
+                    "kind": 12,
+                    "range": range((5, 4), (5, 18)),
+                    "selectionRange": range((5, 8), (5, 13)),
+                    "children": [],
+                }],
+            },
+        ]);
+        assert_eq!(replies[1]["result"], expected);
+    }
+
+    #[test]
+    fn definition_and_hover() {
+        let source = indoc! {"
+            fun greet(name) {
+              var 𝑥 = name;
+              print 𝑥 + suffix + clock();
+            }
+            class A {}
+            var suffix = A;
+            print name
+        "};
+        let replies = session(&[
+            open(source),
+            // A parameter, just after its use.
+            request(1, "textDocument/definition", at(1, 15)),
+            request(2, "textDocument/hover", at(1, 15)),
+            // A local, named with 2 UTF-16 code units.
+            request(3, "textDocument/definition", at(2, 8)),
+            request(4, "textDocument/hover", at(2, 8)),
+            // A global declared later.
+            request(5, "textDocument/definition", at(2, 13)),
+            request(6, "textDocument/hover", at(2, 13)),
+            request(7, "textDocument/hover", at(5, 13)),
+            // A native function, an undefined global, and not a variable.
+            request(8, "textDocument/definition", at(2, 22)),
+            request(9, "textDocument/hover", at(6, 6)),
+            request(10, "textDocument/hover", at(0, 1)),
+        ]);
+        let results: Vec<_> = replies[1..]
+            .iter()
+            .map(|reply| reply["result"].clone())
+            .collect();
+        let location = |start, end| json!({ "uri": URI, "range": range(start, end) });
+        let hover = |value: &str, start, end| {
+            json!({
+                "contents": { "kind": "markdown", "value": format!("```lox\n{value}\n```") },
+                "range": range(start, end),
+            })
+        };
+        assert_eq!(
+            results,
+            [
+                location((0, 10), (0, 14)),
+                hover("(parameter) name", (1, 11), (1, 15)),
+                location((1, 6), (1, 8)),
+                hover("var 𝑥", (2, 8), (2, 10)),
+                location((5, 4), (5, 10)),
+                hover("var suffix", (2, 13), (2, 19)),
+                hover("class A", (5, 13), (5, 14)),
+                Value::Null,
+                Value::Null,
+                Value::Null,
+            ]
+        );
+    }
+
+    #[test]
+    fn analysis_per_version() {
+        let mut document = Document::new(URI, "var a = 1;\nprint a;", 1);
+        let position = Position {
+            line: 1,
+            character: 7,
+        };
+        let hover = |document: &Document| document.hover(position).unwrap().contents.value;
+        assert_eq!(hover(&document), "```lox\nvar a\n```");
+        document.semantic_tokens();
+        assert!(document.analysis.get().is_some());
+        assert!(document.semantic_tokens.get().is_some());
+
+        let range = Range {
+            start: Position {
+                line: 0,
+                character: 0,
+            },
+            end: Position {
+                line: 0,
+                character: 10,
+            },
+        };
+        document.edit(range, "fun a() {}".to_owned());
+        assert!(document.analysis.get().is_none());
+        assert!(document.semantic_tokens.get().is_none());
+        assert_eq!(hover(&document), "```lox\nfun a()\n```");
+    }
+}
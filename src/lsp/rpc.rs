@@ -0,0 +1,178 @@
+//! JSON-RPC 2.0 messages, each framed by a `Content-Length` header as the
+//! Language Server Protocol sends them over stdio.
+
+use std::io::{self, BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The message wasn't valid JSON.
+pub const PARSE_ERROR: i64 = -32700;
+/// The request isn't allowed now, like any request after `shutdown`.
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// A request other than `initialize` came before it.
+pub const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+/// The longest body read, in bytes, so a bad `Content-Length` can't make
+/// the server allocate any amount of memory.
+const MAX_CONTENT_LENGTH: usize = 64 << 20;
+
+/// A message from the client: a request if it has an `id`, which expects a
+/// [`Response`] with the same `id`, or a notification if it doesn't. The
+/// server never sends requests, so messages without a `method`, which are
+/// responses, are ignored.
+#[derive(Debug, Deserialize)]
+pub struct Message {
+    pub id: Option<Value>,
+    pub method: Option<String>,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Outcome {
+    Result(Value),
+    Error(ResponseError),
+}
+
+impl Response {
+    pub fn new(id: Value, result: Result<Value, ResponseError>) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            outcome: match result {
+                Ok(result) => Outcome::Result(result),
+                Err(error) => Outcome::Error(error),
+            },
+        }
+    }
+}
+
+/// Why a request failed, with one of the codes above.
+#[derive(Debug, Serialize)]
+pub struct ResponseError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl ResponseError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// A message from the server that expects no response.
+#[derive(Debug, Serialize)]
+pub struct Notification<P> {
+    jsonrpc: &'static str,
+    method: &'static str,
+    params: P,
+}
+
+impl<P> Notification<P> {
+    pub fn new(method: &'static str, params: P) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            method,
+            params,
+        }
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Read the body of the next message, or return `None` at the end of input.
+pub fn read(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        // Other headers, like `Content-Type`, only ever have one value.
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            let value = value.trim();
+            length = Some(
+                value
+                    .parse()
+                    .map_err(|_| invalid_data(format!("invalid Content-Length {value}")))?,
+            );
+        }
+    }
+    let length = length.ok_or_else(|| invalid_data("message without a Content-Length"))?;
+    if length > MAX_CONTENT_LENGTH {
+        return Err(invalid_data(format!(
+            "Content-Length {length} is over the limit of {MAX_CONTENT_LENGTH}"
+        )));
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|e| invalid_data(e.to_string()))
+}
+
+/// Write `message` with its header, and flush it so the client gets it now.
+pub fn write(output: &mut impl Write, message: &impl Serialize) -> io::Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn framing() {
+        let mut output = Vec::new();
+        write(&mut output, &Notification::new("exit", json!(null))).unwrap();
+        write(
+            &mut output,
+            &Response::new(json!(1), Err(ResponseError::new(METHOD_NOT_FOUND, "é"))),
+        )
+        .unwrap();
+        let mut input = &output[..];
+        assert_eq!(
+            read(&mut input).unwrap().unwrap(),
+            r#"{"jsonrpc":"2.0","method":"exit","params":null}"#
+        );
+        // The length is in bytes, not chars.
+        assert_eq!(
+            read(&mut input).unwrap().unwrap(),
+            r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"é"}}"#
+        );
+        assert_eq!(read(&mut input).unwrap(), None);
+
+        let mut input = "Content-Type: x\r\n\r\n{}".as_bytes();
+        assert!(read(&mut input).is_err());
+
+        let header = format!("Content-Length: {}\r\n\r\n", MAX_CONTENT_LENGTH + 1);
+        let error = read(&mut header.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
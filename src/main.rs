@@ -1,43 +1,183 @@
+use std::env;
 use std::fs;
-use std::io;
 use std::io::prelude::*;
+use std::io::{self, IsTerminal};
+use std::process;
+use std::rc::Rc;
 
 use anyhow::Result;
 use camino::Utf8PathBuf;
-use clap::{CommandFactory, Parser as ArgParser, error::ErrorKind::ValueValidation};
+use clap::{
+    CommandFactory, Parser as ArgParser, Subcommand, ValueEnum, error::ErrorKind::ValueValidation,
+};
+use lox::diagnostic::Renderer;
+use lox::emit;
+use lox::error::Error;
+use lox::error_codes;
+use lox::format;
+use lox::interpreter::Interpreter;
+use lox::lsp;
 use lox::parser::Parser;
-use lox::scanner::Scanner;
+use lox::resolver::Resolver;
+use lox::source::{Source, SourceMap};
+use lox::stmt::Stmt;
+use lox::syntax::{self, Parse};
+use lox::token::Token;
+use typed_arena::Arena;
 
 /// Lox interpreter from Crafting Interpreters
 #[derive(ArgParser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Lox file to interpret
     file: Option<Utf8PathBuf>,
+    /// How to print errors
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Describe an error code, like E0001, with examples
+    Explain { code: String },
+    /// Format Lox files in place, or stdin to stdout if no files are given
+    Fmt {
+        /// Lox files to format
+        files: Vec<Utf8PathBuf>,
+        /// Only check formatting: list files that would change, and exit with
+        /// status 1 if there are any
+        #[arg(long)]
+        check: bool,
+    },
+    /// Run a Language Server Protocol server, talking JSON-RPC over stdin and
+    /// stdout, for editors
+    Lsp,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ErrorFormat {
+    /// Annotated source, in colour on terminals
+    Human,
+    /// One JSON record per line
+    Json,
+    /// A SARIF 2.1.0 log
+    Sarif,
 }
 
 fn main() -> Result<()> {
     env_logger::init();
     let args = Args::parse();
 
+    match args.command {
+        Some(Command::Explain { code }) => return explain(&code),
+        Some(Command::Fmt { files, check }) => return fmt(&files, check),
+        Some(Command::Lsp) => return lsp(),
+        None => {}
+    }
     if let Some(file) = args.file {
         if !file.exists() {
             Args::command()
                 .error(ValueValidation, format!("file {file} does not exist"))
                 .exit();
         }
-        run_file(&file)
+        run_file(&file, args.error_format)
     } else {
-        run_repl()
+        run_repl(args.error_format)
+    }
+}
+
+fn explain(code: &str) -> Result<()> {
+    match error_codes::explain(code) {
+        Some(explanation) => {
+            print!("{explanation}");
+            Ok(())
+        }
+        None => Args::command()
+            .error(ValueValidation, format!("no error code {code}"))
+            .exit(),
+    }
+}
+
+/// Format `files` in place, or stdin to stdout if there are none. With
+/// `check`, nothing is written, and the files that would change are listed.
+fn fmt(files: &[Utf8PathBuf], check: bool) -> Result<()> {
+    let mut sources = SourceMap::new();
+    let mut errors = Vec::new();
+    let mut unformatted = false;
+
+    if files.is_empty() {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        let source = sources.add("<stdin>", &input);
+        match format::format(&source) {
+            Ok(formatted) if check => unformatted = formatted != input,
+            Ok(formatted) => print!("{formatted}"),
+            Err(e) => errors.extend(e),
+        }
+    }
+    for file in files {
+        let input = fs::read_to_string(file)?;
+        let source = sources.add(file, &input);
+        match format::format(&source) {
+            Ok(formatted) if formatted == input => {}
+            Ok(_) if check => {
+                println!("{file}");
+                unformatted = true;
+            }
+            Ok(formatted) => fs::write(file, formatted)?,
+            Err(e) => errors.extend(e),
+        }
+    }
+
+    if !errors.is_empty() {
+        let failure = Failure {
+            errors,
+            before_running: true,
+        };
+        let exit_code = failure.exit_code();
+        failure.report(&sources, ErrorFormat::Human);
+        process::exit(exit_code);
+    }
+    if unformatted {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Serve the Language Server Protocol on stdin and stdout. Exits with status 1
+/// if the client exits without asking the server to shut down first.
+fn lsp() -> Result<()> {
+    if !lsp::serve(io::stdin().lock(), io::stdout().lock())? {
+        process::exit(1);
     }
+    Ok(())
 }
 
-fn run_file(file: &Utf8PathBuf) -> Result<()> {
+/// Exit code for errors in the input, from sysexits.h.
+const EX_DATAERR: i32 = 65;
+/// Exit code for errors while running, from sysexits.h.
+const EX_SOFTWARE: i32 = 70;
+
+fn run_file(file: &Utf8PathBuf, format: ErrorFormat) -> Result<()> {
     let input = fs::read_to_string(file)?;
-    run(input)
+    let mut sources = SourceMap::new();
+    let source = sources.add(file, &input);
+    let arenas = Arenas::default();
+    let mut interpreter = Interpreter::new();
+    if let Err(failure) = run(&arenas, &mut interpreter, source, false) {
+        let exit_code = failure.exit_code();
+        failure.report(&sources, format);
+        process::exit(exit_code);
+    }
+    Ok(())
 }
 
-fn run_repl() -> Result<()> {
+fn run_repl(format: ErrorFormat) -> Result<()> {
+    let mut sources = SourceMap::new();
+    let arenas = Arenas::default();
+    let mut interpreter = Interpreter::new();
     loop {
         let Some(line) = readline()? else {
             break;
@@ -46,25 +186,141 @@ fn run_repl() -> Result<()> {
         if line.is_empty() {
             continue;
         }
-        let result = run(line);
-        if let Err(e) = result {
-            eprintln!("{e}");
+        // Each line is its own virtual file, so errors in code defined by
+        // earlier lines still point at them.
+        let path = format!("<repl:{}>", sources.len() + 1);
+        let source = sources.add(path, &line);
+        if let Err(failure) = run(&arenas, &mut interpreter, source, true) {
+            failure.report(&sources, format);
         }
-        // match result {
-        //     Ok(_) => println!(" => TODO"),
-        //     Err(e) => ,
-        // }
     }
     Ok(())
 }
 
-fn run(input: String) -> Result<()> {
-    let scanner = Scanner::new(&input);
-    let tokens = scanner.tokens();
-    let mut parser = Parser::new(&tokens);
-    let ast = parser.parse()?;
-    println!("{ast}");
-    Ok(())
+/// Errors that stopped a run.
+struct Failure {
+    errors: Vec<Error>,
+    /// Whether the errors were found before running, by the parser or
+    /// resolver.
+    before_running: bool,
+}
+
+impl Failure {
+    fn exit_code(&self) -> i32 {
+        if self.before_running {
+            EX_DATAERR
+        } else {
+            EX_SOFTWARE
+        }
+    }
+
+    /// Print every error to stderr in `format`, sorted by position.
+    fn report(mut self, sources: &SourceMap, format: ErrorFormat) {
+        self.errors
+            .sort_by_key(|e| (e.span().file.0, e.span().start));
+        let diagnostics: Vec<_> = self.errors.iter().map(Error::diagnostic).collect();
+        match format {
+            ErrorFormat::Human => {
+                let renderer = if io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none() {
+                    Renderer::colored()
+                } else {
+                    Renderer::plain()
+                };
+                for diagnostic in &diagnostics {
+                    eprintln!("{}", renderer.render(diagnostic, sources));
+                }
+            }
+            ErrorFormat::Json => {
+                for diagnostic in &diagnostics {
+                    eprintln!("{}", emit::json(diagnostic, sources));
+                }
+            }
+            ErrorFormat::Sarif => eprintln!("{}", emit::sarif(&diagnostics, sources)),
+        }
+    }
+}
+
+/// The sources run by an interpreter, with their tokens and statements,
+/// which values defined by them, like functions, keep borrowing for as long
+/// as the interpreter lives.
+#[derive(Default)]
+struct Arenas<'a> {
+    sources: Arena<Rc<Source>>,
+    tokens: Arena<Vec<Token>>,
+    statements: Arena<Vec<Stmt<'a>>>,
+}
+
+/// Scan, parse, resolve and interpret `source`. If `echo` is set and the
+/// source is a single expression, print its value.
+///
+/// Only what's run goes in `arenas`: tokens once they parse, and statements
+/// once they resolve.
+fn run<'a>(
+    arenas: &'a Arenas<'a>,
+    interpreter: &mut Interpreter<'a>,
+    source: Rc<Source>,
+    echo: bool,
+) -> Result<(), Failure> {
+    let source: &Source = arenas.sources.alloc(source);
+    let failure = |errors, before_running| Failure {
+        errors,
+        before_running,
+    };
+
+    let statements = parse(arenas, source, echo).map_err(|errors| failure(errors, true))?;
+
+    let resolver = Resolver::new(source.as_str());
+    let locals = resolver
+        .resolve(&statements)
+        .map_err(|errors| failure(errors, true))?;
+    interpreter.resolve(locals);
+
+    let statements = arenas.statements.alloc(statements);
+    interpreter
+        .interpret(source.as_str(), statements)
+        .map_err(|e| failure(vec![e], false))
+}
+
+/// Parse `source` into statements, borrowing tokens put in `arenas`. If
+/// `echo` is set and the source is a single expression, it's parsed into a
+/// statement printing it.
+///
+/// If the source is neither, the errors are those of whichever parse got
+/// further before its first error, or the expression's if neither did: a
+/// line like `1 + 2 3` is meant as an expression, `print 1 +;` isn't.
+fn parse<'a>(
+    arenas: &'a Arenas<'a>,
+    source: &'a Source,
+    echo: bool,
+) -> Result<Vec<Stmt<'a>>, Vec<Error>> {
+    // A tree with errors isn't lowered, so its tokens aren't kept.
+    let tokens = |syntax: &Parse| match syntax.errors() {
+        [] => Ok(&arenas.tokens.alloc(syntax.tokens())[..]),
+        errors => Err(errors.to_vec()),
+    };
+    let statements = || {
+        let syntax = syntax::parse_with_file(source.as_str(), source.id());
+        Parser::new(&syntax, tokens(&syntax)?).parse()
+    };
+    if !echo {
+        return statements();
+    }
+    let syntax = syntax::parse_expression(source.as_str(), source.id());
+    let expression_errors = match tokens(&syntax) {
+        Ok(tokens) => {
+            let expression = Parser::new(&syntax, tokens).parse_expression()?;
+            return Ok(vec![Stmt::Print { expression }]);
+        }
+        Err(errors) => errors,
+    };
+    statements().map_err(|statement_errors| {
+        let first_error = |errors: &[Error]| errors.first().map(|e| e.span().start);
+        if first_error(&statement_errors) > first_error(&expression_errors) {
+            statement_errors
+        } else {
+            expression_errors
+        }
+    })
 }
 
 /// Returns a result with Some(text) or None indicating EOF.
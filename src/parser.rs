@@ -1,181 +1,376 @@
 use crate::error::Error;
-use std::iter::Peekable;
-use std::slice::Iter;
+use std::mem;
+use std::sync::LazyLock;
 
 use crate::expr::Expr;
+use crate::source::Span;
+use crate::stack;
+use crate::stmt::{FunctionDecl, Stmt};
+use crate::syntax::ast::{self, AstNode};
+use crate::syntax::{Parse, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
 use crate::token::{Keyword, Token, TokenKind};
 
+/// The implicit condition of a `for` loop without one.
+static TRUE: LazyLock<Token> = LazyLock::new(|| Token::new_keyword(0, Keyword::True));
+
+/// Builds the tree the resolver and interpreter run by lowering a
+/// [`syntax`](crate::syntax) tree, whose parser owns the grammar and the
+/// recovery from errors. Where it found errors, the lowered tree has
+/// [`Stmt::Error`] and [`Expr::Error`] nodes.
 pub struct Parser<'tok> {
-    tokens: Peekable<Iter<'tok, Token>>,
+    root: SyntaxNode,
+    /// The tokens of the syntax tree, which the lowered tree borrows.
+    tokens: &'tok [Token],
+    /// Errors found parsing the syntax tree.
+    errors: Vec<Error>,
 }
 
-/// Recursive descent parser
 impl<'tok> Parser<'tok> {
-    pub fn new(tokens: &'tok [Token]) -> Self {
+    /// Lower `syntax`, whose tokens, from [`Parse::tokens`], are `tokens`.
+    pub fn new(syntax: &Parse, tokens: &'tok [Token]) -> Self {
         Self {
-            tokens: tokens.iter().peekable(),
+            root: syntax.syntax(),
+            tokens,
+            errors: syntax.errors().to_vec(),
         }
     }
 
-    pub fn parse(&mut self) -> Result<Box<Expr<'tok>>, Error> {
-        self.expression()
+    /// Lower the whole tree, or return every error found parsing it.
+    pub fn parse(&mut self) -> Result<Vec<Stmt<'tok>>, Vec<Error>> {
+        let (statements, errors) = self.parse_partial();
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors)
+        }
     }
 
-    /// expression -> equality ;
-    fn expression(&mut self) -> Result<Box<Expr<'tok>>, Error> {
-        self.equality()
+    /// Lower the whole tree, returning it along with every error found
+    /// parsing it.
+    pub fn parse_partial(&mut self) -> (Vec<Stmt<'tok>>, Vec<Error>) {
+        let statements = self.statements(&self.root);
+        (statements, mem::take(&mut self.errors))
     }
 
-    /// equality -> comparison ( ( "!=" | "==" ) comparison )* ;
-    fn equality(&mut self) -> Result<Box<Expr<'tok>>, Error> {
-        let mut expr = self.comparison()?;
-
-        while let Some(TokenKind::BangEqual | TokenKind::EqualEqual) =
-            self.tokens.peek().map(|tok| tok.kind())
-        {
-            let operator = self.tokens.next().unwrap();
-            let right = self.comparison()?;
-            expr = Box::new(Expr::Binary {
-                left: expr,
-                operator,
-                right,
-            });
+    /// Lower a tree from [`syntax::parse_expression`], a single expression
+    /// spanning the whole input.
+    ///
+    /// [`syntax::parse_expression`]: crate::syntax::parse_expression
+    pub fn parse_expression(&mut self) -> Result<Box<Expr<'tok>>, Vec<Error>> {
+        let expression = self.root_expression();
+        if self.errors.is_empty() {
+            Ok(expression)
+        } else {
+            Err(mem::take(&mut self.errors))
         }
+    }
+
+    fn root_expression(&self) -> Box<Expr<'tok>> {
+        self.operand(&self.root, 0)
+    }
 
-        Ok(expr)
+    /// Returns the token `token` of the syntax tree stands for.
+    fn token(&self, token: &SyntaxToken) -> &'tok Token {
+        let tokens = self.tokens;
+        let start = token.span().start;
+        let index = tokens
+            .binary_search_by_key(&start, |tok| tok.span().start)
+            .expect("the tokens are the syntax tree's");
+        &tokens[index]
     }
 
-    /// comparison -> term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
-    fn comparison(&mut self) -> Result<Box<Expr<'tok>>, Error> {
-        let mut expr = self.term()?;
+    /// Lower the declarations in `parent`, a program or block.
+    fn statements(&self, parent: &SyntaxNode) -> Vec<Stmt<'tok>> {
+        parent
+            .children()
+            .map(|node| self.statement(&node))
+            .collect()
+    }
 
-        while let Some(
-            TokenKind::Greater | TokenKind::GreaterEqual | TokenKind::Less | TokenKind::LessEqual,
-        ) = self.tokens.peek().map(|tok| tok.kind())
-        {
-            let operator = self.tokens.next().unwrap();
-            let right = self.term()?;
-            expr = Box::new(Expr::Binary {
-                left: expr,
-                operator,
-                right,
-            });
+    /// Lower the `n`th child node of `parent`, a statement.
+    fn nested(&self, parent: &SyntaxNode, n: usize) -> Stmt<'tok> {
+        match parent.children().nth(n) {
+            Some(node) => self.statement(&node),
+            None => Stmt::Error {
+                span: missing(parent),
+            },
         }
-
-        Ok(expr)
     }
 
-    /// term -> factor ( ( "- | "+" ) factor )* ;
-    fn term(&mut self) -> Result<Box<Expr<'tok>>, Error> {
-        let mut expr = self.factor()?;
+    /// Lower a declaration, or return an error node covering it if it's an
+    /// error node itself, or a part it can't do without is missing.
+    fn statement(&self, node: &SyntaxNode) -> Stmt<'tok> {
+        stack::grow(|| self.try_statement(node))
+            .unwrap_or_else(|| Stmt::Error { span: node.span() })
+    }
 
-        while let Some(TokenKind::Minus | TokenKind::Plus) =
-            self.tokens.peek().map(|tok| tok.kind())
-        {
-            let operator = self.tokens.next().unwrap();
-            let right = self.factor()?;
-            expr = Box::new(Expr::Binary {
-                left: expr,
-                operator,
-                right,
-            });
-        }
+    /// Lower a declaration or statement node into a [`Stmt`], or return
+    /// `None` if it's an error node, or a part it can't do without is missing.
+    fn try_statement(&self, node: &SyntaxNode) -> Option<Stmt<'tok>> {
+        let stmt = match node.kind() {
+            SyntaxKind::ClassDecl => {
+                let class = ast::ClassDecl::cast(node.clone())?;
+                Stmt::Class {
+                    name: self.token(&class.name()?),
+                    superclass: class.superclass().map(|name| {
+                        Box::new(Expr::Variable {
+                            name: self.token(&name),
+                        })
+                    }),
+                    methods: class
+                        .methods()
+                        .filter_map(|method| self.function(&method))
+                        .collect(),
+                }
+            }
+            SyntaxKind::FunDecl => {
+                let function = ast::FunDecl::cast(node.clone())?.function()?;
+                Stmt::Function {
+                    declaration: self.function(&function)?,
+                }
+            }
+            SyntaxKind::VarDecl => Stmt::Var {
+                name: self.token(&ast::VarDecl::cast(node.clone())?.name()?),
+                initializer: node.children().next().map(|node| self.expression(&node)),
+            },
+            SyntaxKind::ExprStmt => Stmt::Expression {
+                expression: self.operand(node, 0),
+            },
+            SyntaxKind::ForStmt => self.for_statement(&ast::ForStmt::cast(node.clone())?),
+            SyntaxKind::IfStmt => Stmt::If {
+                condition: self.operand(node, 0),
+                then_branch: Box::new(self.nested(node, 1)),
+                else_branch: node
+                    .children()
+                    .nth(2)
+                    .map(|node| Box::new(self.statement(&node))),
+            },
+            SyntaxKind::PrintStmt => Stmt::Print {
+                expression: self.operand(node, 0),
+            },
+            SyntaxKind::ReturnStmt => Stmt::Return {
+                keyword: self.token(&ast::ReturnStmt::cast(node.clone())?.keyword()?),
+                value: node.children().next().map(|node| self.expression(&node)),
+            },
+            SyntaxKind::WhileStmt => Stmt::While {
+                condition: self.operand(node, 0),
+                body: Box::new(self.nested(node, 1)),
+            },
+            SyntaxKind::Block => Stmt::Block {
+                statements: self.statements(node),
+            },
+            _ => return None,
+        };
+        Some(stmt)
+    }
 
-        Ok(expr)
+    /// Lower a `Function` node, of a function or method, into a
+    /// [`FunctionDecl`].
+    fn function(&self, function: &ast::Function) -> Option<FunctionDecl<'tok>> {
+        Some(FunctionDecl {
+            name: self.token(&function.name()?),
+            params: function
+                .params()
+                .map(|params| params.params().map(|param| self.token(&param)).collect())
+                .unwrap_or_default(),
+            body: function
+                .body()
+                .map(|body| self.statements(body.syntax()))
+                .unwrap_or_default(),
+        })
     }
 
-    /// factor -> unary ( ( "/" | "*" ) unary )* ;
-    fn factor(&mut self) -> Result<Box<Expr<'tok>>, Error> {
-        let mut expr = self.unary()?;
+    /// Lower a `ForStmt` node into a `while` loop, in a block with the
+    /// initializer if it has one.
+    fn for_statement(&self, for_loop: &ast::ForStmt) -> Stmt<'tok> {
+        let condition = match for_loop.condition() {
+            Some(condition) => self.expression(condition.syntax()),
+            None => Box::new(Expr::Literal { value: &TRUE }),
+        };
+        let mut body = match for_loop.body() {
+            Some(body) => self.statement(body.syntax()),
+            None => Stmt::Error {
+                span: missing(for_loop.syntax()),
+            },
+        };
+        if let Some(increment) = for_loop.increment() {
+            body = Stmt::Block {
+                statements: vec![
+                    body,
+                    Stmt::Expression {
+                        expression: self.expression(increment.syntax()),
+                    },
+                ],
+            };
+        }
+        body = Stmt::While {
+            condition,
+            body: Box::new(body),
+        };
+        if let Some(initializer) = for_loop.initializer() {
+            body = Stmt::Block {
+                statements: vec![self.statement(initializer.syntax()), body],
+            };
+        }
+        body
+    }
 
-        while let Some(TokenKind::Slash | TokenKind::Star) =
-            self.tokens.peek().map(|tok| tok.kind())
-        {
-            let operator = self.tokens.next().unwrap();
-            let right = self.unary()?;
-            expr = Box::new(Expr::Binary {
-                left: expr,
-                operator,
-                right,
-            });
+    /// Lower the `n`th child node of `parent`, an expression.
+    fn operand(&self, parent: &SyntaxNode, n: usize) -> Box<Expr<'tok>> {
+        match parent.children().nth(n) {
+            Some(node) => self.expression(&node),
+            None => Box::new(Expr::Error {
+                span: missing(parent),
+            }),
         }
+    }
 
-        Ok(expr)
+    /// Lower an expression, or return an error node covering it if it's an
+    /// error node itself, or a part it can't do without is missing.
+    fn expression(&self, node: &SyntaxNode) -> Box<Expr<'tok>> {
+        Box::new(
+            stack::grow(|| self.try_expression(node))
+                .unwrap_or_else(|| Expr::Error { span: node.span() }),
+        )
     }
 
-    /// unary -> ( "!" | "-" ) unary
-    ///        | primary ;
-    fn unary(&mut self) -> Result<Box<Expr<'tok>>, Error> {
-        let expr = match self.tokens.peek().map(|tok| tok.kind()) {
-            Some(TokenKind::Bang | TokenKind::Minus) => {
-                let operator = self.tokens.next().unwrap();
-                let right = self.unary()?;
-                Box::new(Expr::Unary { operator, right })
+    /// Lower an expression node into an [`Expr`], or return `None` if it's an
+    /// error node, or a part it can't do without is missing. Assignments to a
+    /// property become [`Expr::Set`].
+    fn try_expression(&self, node: &SyntaxNode) -> Option<Expr<'tok>> {
+        // The operator of an operation, or the token of a primary.
+        let first = || first_token(node).map(|token| self.token(&token));
+        let expr = match node.kind() {
+            SyntaxKind::Assign => {
+                let target = node.children().next()?;
+                let value = self.operand(node, 1);
+                // The syntax tree's parser reported any invalid target.
+                match self.try_expression(&target)? {
+                    Expr::Variable { name } => Expr::Assign { name, value },
+                    Expr::Get { object, name } => Expr::Set {
+                        object,
+                        name,
+                        value,
+                    },
+                    target => target,
+                }
             }
-            _ => self.primary()?,
-        };
-        Ok(expr)
-    }
-
-    /// primary -> NUMBER | STRING | "true" | "false" | "nil"
-    ///          | "(" expression ")" ;
-    fn primary(&mut self) -> Result<Box<Expr<'tok>>, Error> {
-        let token = self.tokens.peek();
-        match token.map(|tok| tok.kind()) {
-            Some(
-                TokenKind::Keyword(Keyword::True)
-                | TokenKind::Keyword(Keyword::False)
-                | TokenKind::Keyword(Keyword::Nil)
-                | TokenKind::Number
-                | TokenKind::String,
-            ) => Ok(Box::new(Expr::Literal {
-                value: self.tokens.next().unwrap(),
-            })),
-            Some(TokenKind::LeftParen) => {
-                self.tokens.next(); // consume left parenthesis
-                let expression = self.expression()?;
-                if let Some(TokenKind::RightParen) = self.tokens.next().map(|tok| tok.kind()) {
-                    Ok(Box::new(Expr::Grouping { expression }))
-                } else {
-                    Err(Error::UnclosedParenthesis {
-                        source_line: "FIXME".to_string(),
-                        line_number: 1,
-                        column_number: 1,
+            SyntaxKind::Binary => Expr::Binary {
+                left: self.operand(node, 0),
+                operator: first()?,
+                right: self.operand(node, 1),
+            },
+            SyntaxKind::Logical => Expr::Logical {
+                left: self.operand(node, 0),
+                operator: first()?,
+                right: self.operand(node, 1),
+            },
+            SyntaxKind::Unary => Expr::Unary {
+                operator: first()?,
+                right: self.operand(node, 0),
+            },
+            SyntaxKind::Call => {
+                let arguments = ast::Call::cast(node.clone())?.arguments()?;
+                let arguments = arguments.syntax();
+                // Runtime errors point at the `)`, or the `(` if it's missing.
+                let paren = direct_tokens(arguments)
+                    .find(|token| token.kind() == TokenKind::RightParen)
+                    .or_else(|| first_token(arguments))?;
+                Expr::Call {
+                    callee: self.operand(node, 0),
+                    paren: self.token(&paren),
+                    arguments: arguments
+                        .children()
+                        .map(|argument| *self.expression(&argument))
+                        .collect(),
+                }
+            }
+            SyntaxKind::Get => Expr::Get {
+                object: self.operand(node, 0),
+                name: self.token(&ast::Get::cast(node.clone())?.name()?),
+            },
+            SyntaxKind::Grouping => Expr::Grouping {
+                expression: self.operand(node, 0),
+            },
+            SyntaxKind::Interpolation => Expr::Interpolation {
+                parts: node
+                    .children_with_tokens()
+                    .map(|element| match element {
+                        SyntaxElement::Token(segment) => Expr::Literal {
+                            value: self.token(&segment),
+                        },
+                        SyntaxElement::Node(hole) => *self.expression(&hole),
                     })
+                    .collect(),
+            },
+            SyntaxKind::Literal => Expr::Literal { value: first()? },
+            SyntaxKind::Super => {
+                let node = ast::Super::cast(node.clone())?;
+                Expr::Super {
+                    keyword: self.token(&node.keyword()?),
+                    method: self.token(&node.method()?),
                 }
             }
-            Some(TokenKind::UnterminatedString) => Err(Error::UnterminatedString {
-                source_line: "FIXME".to_string(),
-                line_number: 1,
-                column_number: 1,
-            }),
-            Some(TokenKind::UnterminatedBlockComment) => Err(Error::UnterminatedBlockComment {
-                source_line: "FIXME".to_string(),
-                line_number: 1,
-                column_number: 1,
-            }),
-            _ => Err(Error::ParseError {
-                source_line: "FIXME".to_string(),
-                line_number: 1,
-                column_number: 1,
-            }),
-        }
+            SyntaxKind::This => Expr::This { keyword: first()? },
+            SyntaxKind::Variable => Expr::Variable { name: first()? },
+            _ => return None,
+        };
+        Some(expr)
+    }
+}
+
+/// Returns the tokens directly in `node`, not in its child nodes.
+fn direct_tokens(node: &SyntaxNode) -> impl Iterator<Item = SyntaxToken> + use<> {
+    node.children_with_tokens()
+        .filter_map(SyntaxElement::into_token)
+}
+
+fn first_token(node: &SyntaxNode) -> Option<SyntaxToken> {
+    direct_tokens(node).next()
+}
+
+/// An empty span at the end of `parent`, for a part of it that's missing.
+fn missing(parent: &SyntaxNode) -> Span {
+    let span = parent.span();
+    Span {
+        start: span.end,
+        ..span
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::scanner::Scanner;
+    use indoc::indoc;
 
     use super::*;
+    use crate::source::FileId;
+    use crate::syntax;
+
+    /// Lower `source`, returning each statement written out, and the errors.
+    fn lower(source: &str) -> (Vec<String>, Vec<Error>) {
+        let syntax = syntax::parse(source);
+        let tokens = syntax.tokens();
+        let (statements, errors) = Parser::new(&syntax, &tokens).parse_partial();
+        let statements = statements.iter().map(ToString::to_string).collect();
+        (statements, errors)
+    }
+
+    /// Lower `source` as an expression, returning it written out, and the
+    /// errors.
+    fn lower_expression(source: &str) -> (String, Vec<Error>) {
+        let syntax = syntax::parse_expression(source, FileId::default());
+        let tokens = syntax.tokens();
+        let parser = Parser::new(&syntax, &tokens);
+        (parser.root_expression().to_string(), parser.errors)
+    }
 
     #[test]
     fn primaries() {
-        let literals = vec!["true", "false", "nil", "123", "\"hello\"", "(1 + 2.0)"];
-        let tokens = vec![
-            vec![Token::new_keyword(0, "true")],
-            vec![Token::new_keyword(0, "false")],
-            vec![Token::new_keyword(0, "nil")],
+        let literals = ["true", "false", "nil", "123", "\"hello\"", "(1 + 2.0)"];
+        let tokens = [
+            vec![Token::new_keyword(0, Keyword::True)],
+            vec![Token::new_keyword(0, Keyword::False)],
+            vec![Token::new_keyword(0, Keyword::Nil)],
             vec![Token::new_number(0, "123")],
             vec![Token::new_string(0, "\"hello\"")],
             vec![
@@ -213,21 +408,362 @@ mod tests {
             },
         ];
         for (source, expected) in literals.iter().zip(primaries) {
-            let scanner = Scanner::new(source);
-            let tokens = scanner.tokens();
-            let mut parser = Parser::new(&tokens);
-            let actual = parser.parse().unwrap();
+            let syntax = syntax::parse_expression(source, FileId::default());
+            let tokens = syntax.tokens();
+            let actual = Parser::new(&syntax, &tokens).parse_expression().unwrap();
             assert_eq!(*actual, expected);
         }
     }
 
     #[test]
     fn primary_unbalanced_parens() {
-        let source = "(1 + 2";
-        let scanner = Scanner::new(source);
-        let tokens = scanner.tokens();
-        let mut parser = Parser::new(&tokens);
-        // FIXME:
-        parser.parse().unwrap_err();
+        let (_, actual) = lower_expression("(1 + 2");
+        let expected = vec![Error::UnclosedParenthesis {
+            span: Span {
+                file: FileId(0),
+                start: 0,
+                end: 1,
+            },
+            close: Span {
+                file: FileId(0),
+                start: 6,
+                end: 6,
+            },
+        }];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn error_spans() {
+        let (actual, errors) = lower_expression("1 +\n(2 * )");
+        assert_eq!(actual, "(+ 1 (group (* 2 (error))))");
+        let expected = vec![Error::ParseError {
+            span: Span {
+                file: FileId(0),
+                start: 9,
+                end: 10,
+            },
+        }];
+        assert_eq!(errors, expected);
+    }
+
+    #[test]
+    fn error_at_end_of_input() {
+        let (actual, errors) = lower_expression("1 +");
+        assert_eq!(actual, "(+ 1 (error))");
+        let expected = vec![Error::ParseError {
+            span: Span {
+                file: FileId(0),
+                start: 3,
+                end: 3,
+            },
+        }];
+        assert_eq!(errors, expected);
+    }
+
+    #[test]
+    fn error_nodes() {
+        let syntax = syntax::parse_expression("(1 + )", FileId::default());
+        let tokens = syntax.tokens();
+        let actual = Parser::new(&syntax, &tokens).root_expression();
+        let expected = Expr::Grouping {
+            expression: Box::new(Expr::Binary {
+                left: Box::new(Expr::Literal { value: &tokens[1] }),
+                operator: &tokens[2],
+                right: Box::new(Expr::Error {
+                    span: Span {
+                        file: FileId(0),
+                        start: 5,
+                        end: 5,
+                    },
+                }),
+            }),
+        };
+        assert_eq!(*actual, expected);
+    }
+
+    #[test]
+    fn statements() {
+        let (actual, errors) = lower("print 1 + 2;\n\"expr\";");
+        assert!(errors.is_empty());
+        assert_eq!(actual, ["(print (+ 1 2))", "(; \"expr\")"]);
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let (_, actual) = lower("print 1\nprint 2;");
+        let expected = vec![Error::Expected {
+            expected: "';' after value",
+            span: Span {
+                file: FileId(0),
+                start: 8,
+                end: 13,
+            },
+        }];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn declarations_and_blocks() {
+        let (actual, errors) = lower("var a = 1; var b; { var a = b = 2; a = a + 1; }");
+        assert!(errors.is_empty());
+        let expected = vec![
+            "(var a 1)",
+            "(var b)",
+            "(block (var a (= b 2)) (; (= a (+ a 1))))",
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn invalid_assignment_target() {
+        let (_, actual) = lower("a + b = c;");
+        let expected = vec![Error::InvalidAssignmentTarget {
+            span: Span {
+                file: FileId(0),
+                start: 6,
+                end: 7,
+            },
+        }];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn logical_precedence() {
+        let (actual, _) = lower("a = b or c and d == e;");
+        assert_eq!(actual, ["(; (= a (or b (and c (== d e)))))"]);
+    }
+
+    #[test]
+    fn dangling_else() {
+        let (actual, _) = lower("if (a) if (b) print 1; else print 2;");
+        assert_eq!(actual, ["(if a (if b (print 1) (print 2)))"]);
+    }
+
+    #[test]
+    fn for_desugars_to_while() {
+        let sources = [
+            "for (var i = 0; i < 3; i = i + 1) print i;",
+            "for (;;) print i;",
+        ];
+        let expected = [
+            "(block (var i 0) (while (< i 3) (block (print i) (; (= i (+ i 1))))))",
+            "(while true (print i))",
+        ];
+        for (source, expected) in sources.iter().zip(expected) {
+            let (actual, errors) = lower(source);
+            assert!(errors.is_empty());
+            assert_eq!(actual, [expected]);
+        }
+    }
+
+    #[test]
+    fn functions_and_calls() {
+        let (actual, errors) = lower("fun add(a, b) { return a + b; } print add(1, 2)(3)();");
+        assert!(errors.is_empty());
+        let expected = vec![
+            "(fun add (a b) (return (+ a b)))",
+            "(print (call (call (call add 1 2) 3)))",
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn too_many_arguments() {
+        let arguments = vec!["1"; 256].join(", ");
+        let (_, actual) = lower(&format!("f({arguments});"));
+        let expected = vec![Error::TooManyArguments {
+            max: 255,
+            span: Span {
+                file: FileId(0),
+                start: 767,
+                end: 768,
+            },
+        }];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn classes() {
+        let source = indoc! {r#"
+            class B < A {
+                init(x) { this.x = x; }
+                method() { return super.method(); }
+            }
+            b.c.d = e.f;
+        "#};
+        let (actual, errors) = lower(source);
+        assert!(errors.is_empty());
+        let expected = vec![
+            "(class B < A (init (x) (; (= (. this x) x))) (method () (return (call (super method)))))",
+            "(; (= (. (. b c) d) (. e f)))",
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn error_recovery() {
+        let source = indoc! {"
+            var a = ;
+            print a
+            fun f() {
+                1 +;
+                return 2;
+            }
+            f(1, 2) = 3;
+            print 4;
+        "};
+        let (_, actual) = lower(source);
+        let expected = vec![
+            Error::ParseError {
+                span: Span {
+                    file: FileId(0),
+                    start: 8,
+                    end: 9,
+                },
+            },
+            Error::Expected {
+                expected: "';' after value",
+                span: Span {
+                    file: FileId(0),
+                    start: 18,
+                    end: 21,
+                },
+            },
+            Error::ParseError {
+                span: Span {
+                    file: FileId(0),
+                    start: 35,
+                    end: 36,
+                },
+            },
+            Error::InvalidAssignmentTarget {
+                span: Span {
+                    file: FileId(0),
+                    start: 61,
+                    end: 62,
+                },
+            },
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn recovery_stops_at_block_end() {
+        let (actual, errors) = lower("{ print 1 } print 2;");
+        assert_eq!(actual, ["(block (print 1))", "(print 2)"]);
+        let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, ["expected ';' after value"]);
+
+        // A stray `}` is skipped.
+        let (actual, errors) = lower("} print 1;");
+        assert_eq!(actual, ["(error)", "(print 1)"]);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn invalid_tokens_report_one_error() {
+        let sources = [
+            ("print \"abc", "unterminated string"),
+            ("/* abc", "unterminated comment"),
+            ("print @; print 1;", "invalid character"),
+            ("{ print 1_; }", "invalid number literal"),
+        ];
+        for (source, expected) in sources {
+            let (_, errors) = lower(source);
+            let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+            assert_eq!(errors, [expected], "{source}");
+        }
+    }
+
+    #[test]
+    fn partial_tree() {
+        let source = indoc! {"
+            var a = ;
+            print a
+            fun f() {
+                1 +;
+            print 2;
+        "};
+        let (actual, errors) = lower(source);
+        let expected = vec![
+            "(var a (error))",
+            "(print a)",
+            "(fun f () (; (+ 1 (error))) (print 2))",
+        ];
+        assert_eq!(actual, expected);
+        let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            [
+                "parse error",
+                "expected ';' after value",
+                "parse error",
+                "expected '}' after block"
+            ]
+        );
+
+        // Tokens skipped after an error are an error node.
+        let syntax = syntax::parse("var a = 1 2 3; print a;");
+        let tokens = syntax.tokens();
+        let (statements, _) = Parser::new(&syntax, &tokens).parse_partial();
+        assert_eq!(
+            statements[1],
+            Stmt::Error {
+                span: Span {
+                    file: FileId(0),
+                    start: 10,
+                    end: 14,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn trailing_tokens() {
+        let syntax = syntax::parse_expression("1 + 2 3 4", FileId::default());
+        let tokens = syntax.tokens();
+        let actual = Parser::new(&syntax, &tokens)
+            .parse_expression()
+            .unwrap_err();
+        let expected = vec![Error::UnexpectedToken {
+            span: Span {
+                file: FileId(0),
+                start: 6,
+                end: 7,
+            },
+        }];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn comments_are_trivia() {
+        let (actual, errors) = lower_expression("1 + /* two */ 2 * // three\n 3 // end");
+        assert!(errors.is_empty());
+        assert_eq!(actual, "(+ 1 (* 2 3))");
+
+        let (actual, errors) = lower("// leading\nprint /* inline */ 1; /* trailing */");
+        assert!(errors.is_empty());
+        assert_eq!(actual, ["(print 1)"]);
+    }
+
+    #[test]
+    fn interpolation() {
+        let (actual, errors) = lower_expression(r#""a ${1 + 2} b ${"c${d}"}!""#);
+        assert!(errors.is_empty());
+        assert_eq!(
+            actual,
+            r#"(interpolate "a ${ (+ 1 2) } b ${ (interpolate "c${ d }") }!")"#
+        );
+
+        let (_, actual) = lower(r#"print "a ${b;"#);
+        let expected = vec![Error::UnterminatedInterpolation {
+            span: Span {
+                file: FileId(0),
+                start: 9,
+                end: 11,
+            },
+        }];
+        assert_eq!(actual, expected);
     }
 }
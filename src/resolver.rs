@@ -0,0 +1,498 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::mem;
+
+use crate::error::Error;
+use crate::expr::Expr;
+use crate::stack;
+use crate::stmt::{FunctionDecl, Stmt};
+use crate::token::Token;
+
+/// Identifies a variable reference by the address of its token, which is
+/// unique for as long as the tokens are alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TokenId(*const Token);
+
+impl From<&Token> for TokenId {
+    fn from(token: &Token) -> Self {
+        Self(token)
+    }
+}
+
+/// The number of scopes between each local variable reference and the scope
+/// that declares it. References to globals are absent.
+pub type Locals = HashMap<TokenId, usize>;
+
+/// The token declaring each variable, for every token naming one: references
+/// and declarations themselves. Globals never declared, like native
+/// functions, are absent.
+pub type Definitions<'src> = HashMap<TokenId, &'src Token>;
+
+/// A local variable in scope.
+#[derive(Debug, Clone, Copy)]
+struct Variable<'src> {
+    /// Whether its initializer has finished resolving.
+    defined: bool,
+    /// The token declaring it, or `None` for `this` and `super`.
+    declaration: Option<&'src Token>,
+}
+
+/// The kind of function being resolved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+/// The kind of class being resolved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Static analysis pass that binds each variable reference to the scope it
+/// lives in and reports semantic errors before any code runs.
+pub struct Resolver<'src> {
+    /// The source the resolved tokens were scanned from.
+    source: &'src str,
+    /// Local scopes, innermost last, mapping variable names to variables.
+    scopes: Vec<HashMap<Cow<'src, str>, Variable<'src>>>,
+    /// The first declaration of each global.
+    globals: HashMap<Cow<'src, str>, &'src Token>,
+    /// References not found in any local scope, resolved against `globals`
+    /// at the end, since functions may refer to globals declared after them.
+    unresolved: Vec<(&'src Token, Cow<'src, str>)>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    locals: Locals,
+    definitions: Definitions<'src>,
+    errors: Vec<Error>,
+}
+
+impl<'src> Resolver<'src> {
+    pub fn new(source: &'src str) -> Self {
+        Self {
+            source,
+            scopes: Vec::new(),
+            globals: HashMap::new(),
+            unresolved: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            locals: Locals::new(),
+            definitions: Definitions::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Resolve `statements`, returning the depth of each local variable
+    /// reference or every semantic error found.
+    pub fn resolve(mut self, statements: &[Stmt<'src>]) -> Result<Locals, Vec<Error>> {
+        self.resolve_statements(statements);
+        if self.errors.is_empty() {
+            Ok(self.locals)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    /// Resolve `statements`, which may have errors from parsing, returning
+    /// the declaration of each variable and every semantic error found. For
+    /// tools, like editors, that want all that can be found in broken code.
+    pub fn resolve_partial(mut self, statements: &[Stmt<'src>]) -> (Definitions<'src>, Vec<Error>) {
+        self.resolve_statements(statements);
+        for (token, name) in mem::take(&mut self.unresolved) {
+            if let Some(&declaration) = self.globals.get(&name) {
+                self.definitions.insert(TokenId::from(token), declaration);
+            }
+        }
+        (self.definitions, self.errors)
+    }
+
+    fn resolve_statements(&mut self, statements: &[Stmt<'src>]) {
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+    }
+
+    fn resolve_statement(&mut self, stmt: &Stmt<'src>) {
+        stack::grow(|| match stmt {
+            Stmt::Block { statements } => {
+                self.scopes.push(HashMap::new());
+                self.resolve_statements(statements);
+                self.scopes.pop();
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let enclosing_class = mem::replace(&mut self.current_class, ClassType::Class);
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if let Expr::Variable {
+                        name: superclass_name,
+                    } = superclass.as_ref()
+                        && superclass_name.identifier(self.source) == name.identifier(self.source)
+                    {
+                        self.errors.push(Error::InheritFromSelf {
+                            span: superclass_name.span().clone(),
+                        });
+                    }
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expression(superclass);
+                    self.scopes.push(HashMap::from([(
+                        Cow::Borrowed("super"),
+                        Variable {
+                            defined: true,
+                            declaration: None,
+                        },
+                    )]));
+                }
+
+                self.scopes.push(HashMap::from([(
+                    Cow::Borrowed("this"),
+                    Variable {
+                        defined: true,
+                        declaration: None,
+                    },
+                )]));
+                for method in methods {
+                    let kind = if method.name.identifier(self.source) == "init" {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.resolve_function(method, kind);
+                }
+                self.scopes.pop();
+
+                if superclass.is_some() {
+                    self.scopes.pop();
+                }
+                self.current_class = enclosing_class;
+            }
+            // Already reported by the parser.
+            Stmt::Error { .. } => {}
+            Stmt::Expression { expression } | Stmt::Print { expression } => {
+                self.resolve_expression(expression);
+            }
+            Stmt::Function { declaration } => {
+                // Define eagerly, so the function can refer to itself.
+                self.declare(declaration.name);
+                self.define(declaration.name);
+                self.resolve_function(declaration, FunctionType::Function);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            }
+            Stmt::Return { keyword, value } => {
+                if self.current_function == FunctionType::None {
+                    self.errors.push(Error::ReturnOutsideFunction {
+                        span: keyword.span().clone(),
+                    });
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.errors.push(Error::ReturnValueFromInitializer {
+                            span: keyword.span().clone(),
+                        });
+                    }
+                    self.resolve_expression(value);
+                }
+            }
+            Stmt::Var { name, initializer } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expression(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::While { condition, body } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(body);
+            }
+        })
+    }
+
+    fn resolve_function(&mut self, function: &FunctionDecl<'src>, kind: FunctionType) {
+        let enclosing_function = mem::replace(&mut self.current_function, kind);
+        self.scopes.push(HashMap::new());
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_statements(&function.body);
+        self.scopes.pop();
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_expression(&mut self, expr: &Expr<'src>) {
+        stack::grow(|| match expr {
+            Expr::Assign { name, value } => {
+                self.resolve_expression(value);
+                self.resolve_local(name, name.identifier(self.source));
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expression(callee);
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            Expr::Error { .. } => {}
+            Expr::Get { object, .. } => self.resolve_expression(object),
+            Expr::Grouping { expression } => self.resolve_expression(expression),
+            Expr::Interpolation { parts } => {
+                for part in parts {
+                    self.resolve_expression(part);
+                }
+            }
+            Expr::Literal { .. } => {}
+            Expr::Set { object, value, .. } => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+            }
+            Expr::Super { keyword, .. } => {
+                match self.current_class {
+                    ClassType::None => self.errors.push(Error::SuperOutsideClass {
+                        span: keyword.span().clone(),
+                    }),
+                    ClassType::Class => self.errors.push(Error::SuperWithoutSuperclass {
+                        span: keyword.span().clone(),
+                    }),
+                    ClassType::Subclass => {}
+                }
+                self.resolve_local(keyword, Cow::Borrowed("super"));
+            }
+            Expr::This { keyword } => {
+                if self.current_class == ClassType::None {
+                    self.errors.push(Error::ThisOutsideClass {
+                        span: keyword.span().clone(),
+                    });
+                }
+                self.resolve_local(keyword, Cow::Borrowed("this"));
+            }
+            Expr::Unary { right, .. } => self.resolve_expression(right),
+            Expr::Variable { name } => {
+                let lexeme = name.identifier(self.source);
+                if self
+                    .scopes
+                    .last()
+                    .is_some_and(|scope| scope.get(&lexeme).is_some_and(|var| !var.defined))
+                {
+                    self.errors.push(Error::ReadLocalInOwnInitializer {
+                        span: name.span().clone(),
+                    });
+                }
+                self.resolve_local(name, lexeme);
+            }
+        })
+    }
+
+    /// Declare `name` in the innermost scope, but mark it as not ready for use.
+    fn declare(&mut self, name: &'src Token) {
+        let lexeme = name.identifier(self.source);
+        self.definitions.insert(TokenId::from(name), name);
+        let Some(scope) = self.scopes.last_mut() else {
+            self.globals.entry(lexeme).or_insert(name);
+            return;
+        };
+        let variable = Variable {
+            defined: false,
+            declaration: Some(name),
+        };
+        if scope.insert(lexeme, variable).is_some() {
+            self.errors.push(Error::DuplicateLocal {
+                name: name.lexeme(self.source).to_owned(),
+                span: name.span().clone(),
+            });
+        }
+    }
+
+    /// Mark `name` as ready for use in the innermost scope.
+    fn define(&mut self, name: &'src Token) {
+        let lexeme = name.identifier(self.source);
+        if let Some(variable) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&lexeme))
+        {
+            variable.defined = true;
+        }
+    }
+
+    /// Record the depth of the innermost scope declaring `name`, referenced by
+    /// `token`. Leave it unresolved if it's not found, assuming it's global.
+    fn resolve_local(&mut self, token: &'src Token, name: Cow<'src, str>) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| Some((depth, scope.get(&name)?)));
+        match found {
+            Some((depth, variable)) => {
+                if let Some(declaration) = variable.declaration {
+                    self.definitions.insert(TokenId::from(token), declaration);
+                }
+                self.locals.insert(TokenId::from(token), depth);
+            }
+            None => self.unresolved.push((token, name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use crate::source::{FileId, Span};
+    use crate::syntax;
+
+    use super::*;
+
+    fn resolve(source: &str) -> Result<Vec<usize>, Vec<Error>> {
+        let syntax = syntax::parse(source);
+        let tokens = syntax.tokens();
+        let statements = Parser::new(&syntax, &tokens).parse().unwrap();
+        let locals = Resolver::new(source).resolve(&statements)?;
+        // Report depths in source order.
+        let depths = tokens
+            .iter()
+            .filter_map(|tok| locals.get(&TokenId::from(tok)).copied())
+            .collect();
+        Ok(depths)
+    }
+
+    #[test]
+    fn scope_depths() {
+        let source = "var g; { var a; { var b; a; b; g; fun f() { a = b; } } }";
+        assert_eq!(resolve(source), Ok(vec![1, 0, 2, 1]));
+    }
+
+    #[test]
+    fn this_and_super_depths() {
+        let source = "class A < B { m() { this; super.m; } }";
+        assert_eq!(resolve(source), Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn definitions() {
+        let source = "fun f(a) { var b = a; print b + g; } var g; class C { m() { this; } }";
+        let syntax = syntax::parse(source);
+        let tokens = syntax.tokens();
+        let statements = Parser::new(&syntax, &tokens).parse().unwrap();
+        let (definitions, errors) = Resolver::new(source).resolve_partial(&statements);
+        assert!(errors.is_empty());
+        // Where each token naming a variable starts, and where its
+        // declaration starts, in source order.
+        let starts: Vec<_> = tokens
+            .iter()
+            .filter_map(|tok| {
+                let declaration = definitions.get(&TokenId::from(tok))?;
+                Some((tok.span().start, declaration.span().start))
+            })
+            .collect();
+        let expected = vec![
+            (4, 4),
+            (6, 6),
+            (15, 15),
+            (19, 6),
+            (28, 15),
+            (32, 41),
+            (41, 41),
+            (50, 50),
+        ];
+        assert_eq!(starts, expected);
+    }
+
+    #[test]
+    fn semantic_errors() {
+        let source = indoc::indoc! {"
+            return 1;
+            { var a = a; var b; var b; }
+            print this;
+            class A < A { init() { return 1; } m() { super.m(); } }
+            class B { m() { super.m(); } }
+            fun f() { super.f(); }
+        "};
+        let expected = vec![
+            Error::ReturnOutsideFunction {
+                span: Span {
+                    file: FileId(0),
+                    start: 0,
+                    end: 6,
+                },
+            },
+            Error::ReadLocalInOwnInitializer {
+                span: Span {
+                    file: FileId(0),
+                    start: 20,
+                    end: 21,
+                },
+            },
+            Error::DuplicateLocal {
+                name: String::from("b"),
+                span: Span {
+                    file: FileId(0),
+                    start: 34,
+                    end: 35,
+                },
+            },
+            Error::ThisOutsideClass {
+                span: Span {
+                    file: FileId(0),
+                    start: 45,
+                    end: 49,
+                },
+            },
+            Error::InheritFromSelf {
+                span: Span {
+                    file: FileId(0),
+                    start: 61,
+                    end: 62,
+                },
+            },
+            Error::ReturnValueFromInitializer {
+                span: Span {
+                    file: FileId(0),
+                    start: 74,
+                    end: 80,
+                },
+            },
+            Error::SuperWithoutSuperclass {
+                span: Span {
+                    file: FileId(0),
+                    start: 123,
+                    end: 128,
+                },
+            },
+            Error::SuperOutsideClass {
+                span: Span {
+                    file: FileId(0),
+                    start: 148,
+                    end: 153,
+                },
+            },
+        ];
+        assert_eq!(resolve(source), Err(expected));
+    }
+}
@@ -1,12 +1,23 @@
+use std::ops::Range;
 use std::str::Chars;
 
-use crate::source::PeekableLineColIterator;
-use crate::token::Token;
+use unicode_xid::UnicodeXID;
+
+use crate::source::{Edit, FileId, PeekableLineColIterator, Source};
+use crate::token::{Keyword, Token, TokenKind, parse_number};
 
 #[derive(Debug)]
 pub struct Scanner<'a> {
     source: &'a str,
     chars: PeekableLineColIterator<Chars<'a>>,
+    /// The file the source was loaded from, recorded in every token's span.
+    file: FileId,
+    /// The `${` holes of interpolated strings being scanned, innermost last.
+    /// Each counts the braces opened inside it and not yet closed, so the `}`
+    /// closing the hole can be told apart from one closing a block.
+    holes: Vec<u32>,
+    /// Whether to keep whitespace, see [`Scanner::lossless`].
+    lossless: bool,
 }
 
 /// The maximum number of scan errors to allow before giving up.
@@ -14,20 +25,45 @@ const MAX_SCAN_ERRORS: u32 = 100;
 
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str) -> Self {
+        Self::with_file(source, FileId::default())
+    }
+
+    /// Create a scanner for `source`, loaded as `file` of a [`SourceMap`].
+    ///
+    /// [`SourceMap`]: crate::source::SourceMap
+    pub fn with_file(source: &'a str, file: FileId) -> Self {
         Self {
             source,
             chars: PeekableLineColIterator::new(source.chars()),
+            file,
+            holes: Vec::new(),
+            lossless: false,
         }
     }
 
+    /// Keep whitespace as tokens, and keep scanning after an invalid
+    /// character rather than skipping the rest of its line, so the tokens
+    /// cover every byte of the source.
+    pub fn lossless(mut self) -> Self {
+        self.lossless = true;
+        self
+    }
+
     /// Walk the source and tokenize.
+    ///
+    /// Gives up after too many errors, unless the scanner is lossless.
     pub fn tokens(self) -> Vec<Token> {
-        self.scan(0, |n_errors, token| {
+        let max_errors = if self.lossless {
+            u32::MAX
+        } else {
+            MAX_SCAN_ERRORS
+        };
+        self.scan(0, move |n_errors, token| {
             if token.is_invalid() {
                 *n_errors += 1
             }
 
-            if *n_errors <= MAX_SCAN_ERRORS {
+            if *n_errors <= max_errors {
                 Some(token)
             } else {
                 None
@@ -37,19 +73,204 @@ impl<'a> Scanner<'a> {
     }
 }
 
+/// Update `tokens`, scanned losslessly from a source before `edit`, to the
+/// tokens of `source` after it. Only tokens the edit could have changed are
+/// scanned again: those from just before the edit until the scanner is back
+/// at the start of an old token, in the same state. Tokens before them are
+/// kept, and those after are kept but shifted.
+///
+/// Returns the range of `tokens` that were scanned again.
+pub fn relex(tokens: &mut Vec<Token>, source: &Source, edit: &Edit) -> Range<usize> {
+    let delta = edit.delta();
+    let edit_end = edit.range.start + edit.text.len();
+
+    // Scanning a token peeks up to two chars past it, like the `.5` of
+    // `1.5`, so the token ending before the edit may change too. The one
+    // before that ends at least two chars before the edit.
+    let first = tokens
+        .partition_point(|token| (token.span().end as usize) < edit.range.start)
+        .saturating_sub(1);
+    let start = tokens
+        .get(first)
+        .map_or(0, |token| token.span().start as usize);
+    let mut holes = Vec::new();
+    for token in &tokens[..first] {
+        track_holes(&mut holes, token);
+    }
+
+    let mut scanner = Scanner::with_file(&source.as_str()[start..], source.id()).lossless();
+    scanner.holes = holes.clone();
+    let mut scanned = Vec::new();
+    let mut old = first;
+    let mut old_holes = holes;
+    let resynced = loop {
+        let pos = start + scanner.chars.offset();
+        if pos >= edit_end {
+            // Past the edit, the old tokens are what the scanner would find
+            // from any old token start it's at, in the same state.
+            let old_pos = (pos as isize - delta) as usize;
+            while let Some(token) = tokens.get(old)
+                && (token.span().start as usize) < old_pos
+            {
+                track_holes(&mut old_holes, token);
+                old += 1;
+            }
+            if tokens
+                .get(old)
+                .is_some_and(|token| token.span().start as usize == old_pos)
+                && scanner.holes == old_holes
+            {
+                break true;
+            }
+        }
+        match scanner.next() {
+            Some(token) => scanned.push(token.shifted(start as isize)),
+            None => break false,
+        }
+    };
+
+    let after = if resynced {
+        tokens.split_off(old)
+    } else {
+        Vec::new()
+    };
+    tokens.truncate(first);
+    let range = first..first + scanned.len();
+    tokens.extend(scanned);
+    tokens.extend(after.into_iter().map(|token| token.shifted(delta)));
+    range
+}
+
+/// Update `holes` as [`Scanner::holes`] is by scanning `token`.
+fn track_holes(holes: &mut Vec<u32>, token: &Token) {
+    match token.kind() {
+        TokenKind::LeftBrace => {
+            if let Some(depth) = holes.last_mut() {
+                *depth += 1;
+            }
+        }
+        TokenKind::RightBrace => {
+            if let Some(depth) = holes.last_mut() {
+                *depth -= 1;
+            }
+        }
+        TokenKind::StringHead => holes.push(0),
+        TokenKind::StringTail => {
+            holes.pop();
+        }
+        _ => {}
+    }
+}
+
+impl<'a> Scanner<'a> {
+    /// Scan a string literal, or a segment of an interpolated one, starting
+    /// with the `"` or `}` delimiter already consumed at `pos`. The segment
+    /// ends at a closing `"` or at a `${` opening a hole.
+    fn string(&mut self, pos: usize, src: &'a str, continuation: bool) -> Token {
+        let mut len = 1;
+        loop {
+            match self.chars.next() {
+                None => return Token::new_unterminated_string(pos, src),
+                Some('\\') => {
+                    // Skip the escaped char, which may be a quote. Escapes
+                    // are decoded later, see `Token::string_value`.
+                    len += 1;
+                    len += self.chars.next().map_or(0, char::len_utf8);
+                }
+                Some('"') => {
+                    len += 1;
+                    let lexeme = &src[..len];
+                    return if continuation {
+                        Token::new_string_tail(pos, lexeme)
+                    } else {
+                        Token::new_string(pos, lexeme)
+                    };
+                }
+                Some('$') if self.chars.peek() == Some(&'{') => {
+                    self.chars.next();
+                    len += 2;
+                    self.holes.push(0);
+                    let lexeme = &src[..len];
+                    return if continuation {
+                        Token::new_string_middle(pos, lexeme)
+                    } else {
+                        Token::new_string_head(pos, lexeme)
+                    };
+                }
+                Some(c) => len += c.len_utf8(),
+            }
+        }
+    }
+}
+
+/// Identifiers follow UAX #31: they start with `_` or an XID_Start char, and
+/// continue with XID_Continue chars. ASCII is checked first, as the common case.
+fn is_identifier_start(c: char) -> bool {
+    if c.is_ascii() {
+        c == '_' || c.is_ascii_alphabetic()
+    } else {
+        c.is_xid_start()
+    }
+}
+
+fn is_identifier_continue(c: char) -> bool {
+    if c.is_ascii() {
+        c == '_' || c.is_ascii_alphanumeric()
+    } else {
+        c.is_xid_continue()
+    }
+}
+
 impl<'a> Iterator for Scanner<'a> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let c = self.chars.by_ref().find(|c| !c.is_whitespace())?;
-        let pos = self.chars.offset() - 1;
+        self.scan_token().map(|token| token.in_file(self.file))
+    }
+}
+
+impl Scanner<'_> {
+    fn scan_token(&mut self) -> Option<Token> {
+        let c = if self.lossless {
+            self.chars.next()?
+        } else {
+            self.chars.by_ref().find(|c| !c.is_whitespace())?
+        };
+        let pos = self.chars.offset() - c.len_utf8();
         let src = &self.source[pos..];
 
         let token = match c {
+            c if c.is_whitespace() => {
+                // Only reached by lossless scanners.
+                let mut len = c.len_utf8();
+                while let Some(&c) = self.chars.peek()
+                    && c.is_whitespace()
+                {
+                    self.chars.next();
+                    len += c.len_utf8();
+                }
+                Token::new_whitespace(pos, &src[..len])
+            }
             '(' => Token::new_left_paren(pos),
             ')' => Token::new_right_paren(pos),
-            '{' => Token::new_left_brace(pos),
-            '}' => Token::new_right_brace(pos),
+            '{' => {
+                if let Some(depth) = self.holes.last_mut() {
+                    *depth += 1;
+                }
+                Token::new_left_brace(pos)
+            }
+            '}' => match self.holes.last_mut() {
+                Some(0) => {
+                    // Closes a hole, resume the string.
+                    self.holes.pop();
+                    self.string(pos, src, true)
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    Token::new_right_brace(pos)
+                }
+                None => Token::new_right_brace(pos),
+            },
             ',' => Token::new_comma(pos),
             '.' => Token::new_dot(pos),
             '-' => Token::new_minus(pos),
@@ -85,7 +306,7 @@ impl<'a> Iterator for Scanner<'a> {
                     self.chars.next();
                     Token::new_greater_equal(pos)
                 } else {
-                    Token::new_equal(pos)
+                    Token::new_greater(pos)
                 }
             }
             '/' => {
@@ -94,12 +315,13 @@ impl<'a> Iterator for Scanner<'a> {
                         // Line comment, consume to the end of the line.
 
                         let len = if let Some(line_len) = src.find('\n') {
-                            self.chars.nth(line_len);
+                            // Leave the newline, accounting for leading `/`.
+                            self.chars.nth(src[1..line_len].chars().count() - 1);
                             line_len
                         } else {
-                            // Line must end the file. Count remaining chars,
-                            // accounting for leading `/`.
-                            self.chars.by_ref().count() + 1
+                            // Line must end the file.
+                            self.chars.by_ref().count();
+                            src.len()
                         };
 
                         Token::new_line_comment(pos, &src[..len])
@@ -124,12 +346,13 @@ impl<'a> Iterator for Scanner<'a> {
 
                             len += next_pos.unwrap() + 1;
 
-                            match &src[(len - 1)..(len + 1)] {
-                                "/*" => {
+                            // Bytes, since the next char may be multibyte.
+                            match &src.as_bytes()[(len - 1)..(len + 1)] {
+                                b"/*" => {
                                     len += 1;
                                     depth += 1;
                                 }
-                                "*/" => {
+                                b"*/" => {
                                     len += 1;
                                     depth -= 1;
                                     if depth == 0 {
@@ -140,87 +363,86 @@ impl<'a> Iterator for Scanner<'a> {
                             }
                         }
 
-                        // Move scanner past comment. Account for start len 2.
-                        self.chars.nth(len - 2);
+                        // Move scanner past comment, accounting for leading
+                        // `/`. The scanner walks chars, not bytes.
+                        self.chars.nth(src[1..len].chars().count() - 1);
 
                         Token::new_block_comment(pos, &src[..len])
                     }
                     _ => Token::new_slash(pos),
                 }
             }
-            '"' => {
-                // String literal.
+            '"' => self.string(pos, src, false),
+            '0'..='9' => {
+                // Number literal. Take the longest run that looks like part of
+                // a number, so malformed ones like `1__0` or `0x` become a
+                // single invalid token, not several valid ones.
 
+                let radix_prefixed = c == '0'
+                    && matches!(self.chars.peek(), Some('x' | 'X' | 'b' | 'B' | 'o' | 'O'));
                 let mut len = 1;
+                let mut prev = c;
+                let mut lookahead = self.chars.clone();
 
                 loop {
-                    let Some(quote_pos) = src[len..].find('"') else {
-                        self.chars.by_ref().count(); // drain scanner
-                        return Some(Token::new_unterminated_string(pos, src));
+                    let part_of_number = match lookahead.peek() {
+                        Some(&c) => {
+                            c.is_ascii_alphanumeric()
+                                || c == '_'
+                                // A fraction, but not a method-like `1.foo`.
+                                || c == '.' && !radix_prefixed && {
+                                    let mut after_dot = lookahead.clone();
+                                    after_dot.next();
+                                    after_dot.peek().is_some_and(|c| c.is_ascii_digit())
+                                }
+                                // An exponent's sign, like `1e-9`.
+                                || matches!(c, '+' | '-')
+                                    && !radix_prefixed
+                                    && matches!(prev, 'e' | 'E')
+                        }
+                        None => false,
                     };
-
-                    len += quote_pos + 1;
-                    self.chars.nth(quote_pos + 1); // move scanner past this quote
-
-                    // If quote was escaped, keep parsing, otherwise done. The
-                    // position of the final quote is `len - 1`. Look for
-                    // escaping `\` just before that.
-                    if src.as_bytes()[len - 2] != b'\\' {
+                    if !part_of_number {
                         break;
                     }
-                }
-
-                Token::new_string(pos, &src[..len])
-            }
-            '0'..='9' => {
-                // Number literal.
-
-                let mut len = 1;
-                let mut lookahead = self.chars.clone();
-
-                while lookahead.peek().is_some_and(|c| c.is_ascii_digit()) {
-                    lookahead.next();
+                    prev = lookahead.next().unwrap();
                     len += 1;
                 }
 
-                if lookahead.next().is_some_and(|c| c == '.')
-                    && lookahead.peek().is_some_and(|c| c.is_ascii_digit())
-                {
-                    len += lookahead.take_while(|c| c.is_ascii_digit()).count() + 1;
-                }
-
                 if len > 1 {
                     self.chars.nth(len - 2); // advance scanner past number
                 }
 
-                Token::new_number(pos, &src[..len])
+                let lexeme = &src[..len];
+                if parse_number(lexeme).is_some() {
+                    Token::new_number(pos, lexeme)
+                } else {
+                    Token::new_invalid_number(pos, lexeme)
+                }
             }
-            c if c == '_' || c.is_ascii_alphabetic() => {
+            c if is_identifier_start(c) => {
                 // Reserved words and identifiers.
-                let mut len = 1;
-                let lookahead = self.chars.clone();
-
-                len += lookahead
-                    .take_while(|c| *c == '_' || c.is_ascii_alphanumeric())
-                    .count();
-                if len > 1 {
-                    self.chars.nth(len - 2); // advance source past symbol
+                let mut len = c.len_utf8();
+                while let Some(&c) = self.chars.peek()
+                    && is_identifier_continue(c)
+                {
+                    self.chars.next();
+                    len += c.len_utf8();
                 }
 
                 let lexeme = &src[..len];
-                match lexeme {
-                    "and" | "class" | "else" | "false" | "fun" | "for" | "if" | "nil" | "or"
-                    | "print" | "return" | "super" | "this" | "true" | "var" | "while" => {
-                        Token::new_keyword(pos, lexeme)
-                    }
-                    _ => Token::new_identifier(pos, lexeme),
+                match Keyword::from_str(lexeme) {
+                    Some(keyword) => Token::new_keyword(pos, keyword),
+                    None => Token::new_identifier(pos, lexeme),
                 }
             }
             _ => {
-                let token = Token::new_invalid_character(pos, &src[..1]);
+                let token = Token::new_invalid_character(pos, &src[..c.len_utf8()]);
 
                 // Consume to the end of the line and keep lexin'.
-                self.chars.by_ref().take_while(|c| *c != '\n').count();
+                if !self.lossless {
+                    self.chars.by_ref().take_while(|c| *c != '\n').count();
+                }
 
                 token
             }
@@ -239,7 +461,7 @@ mod tests {
 
     #[test]
     fn single_and_double_lexemes() {
-        let source = "!=<=>===({,.-=;*})";
+        let source = "!=<=>===({,.-=;*})><";
         let scanner = Scanner::new(source);
         let actual: Vec<_> = scanner.tokens().iter().map(|tok| tok.kind()).collect();
         let expected = vec![
@@ -257,10 +479,26 @@ mod tests {
             TokenKind::Star,
             TokenKind::RightBrace,
             TokenKind::RightParen,
+            TokenKind::Greater,
+            TokenKind::Less,
         ];
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn lossless() {
+        let source = "var a = 1; // one\n\t@ 2";
+        let tokens = Scanner::new(source).lossless().tokens();
+        let lexemes: Vec<_> = tokens.iter().map(|tok| tok.lexeme(source)).collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                "var", " ", "a", " ", "=", " ", "1", ";", " ", "// one", "\n\t", "@", " ", "2"
+            ]
+        );
+        assert_eq!(tokens[1].kind(), TokenKind::Whitespace);
+    }
+
     #[test]
     fn line_comment_eof() {
         let source = "// this is a comment line";
@@ -283,11 +521,15 @@ mod tests {
     fn line_comment_ends_at_eol() {
         let source = indoc! {
             r#"// eol comment
+            nil
             "#
         };
         let scanner = Scanner::new(source);
         let actual = scanner.tokens();
-        let expected = vec![Token::new_line_comment(0, "// eol comment")];
+        let expected = vec![
+            Token::new_line_comment(0, "// eol comment"),
+            Token::new_keyword(15, Keyword::Nil),
+        ];
         assert_eq!(actual, expected);
     }
 
@@ -361,6 +603,63 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn string_literal_followed_by_tokens() {
+        let source = r#"print "hi"; "#;
+        let scanner = Scanner::new(source);
+        let actual = scanner.tokens();
+        let expected = vec![
+            Token::new_keyword(0, Keyword::Print),
+            Token::new_string(6, "\"hi\""),
+            Token::new_semicolon(10),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn escaped_quotes_and_backslashes() {
+        let source = r#""a\"b" "\\" "c\\\"";"#;
+        let scanner = Scanner::new(source);
+        let actual = scanner.tokens();
+        let expected = vec![
+            Token::new_string(0, r#""a\"b""#),
+            Token::new_string(7, r#""\\""#),
+            Token::new_string(12, r#""c\\\"""#),
+            Token::new_semicolon(19),
+        ];
+        assert_eq!(actual, expected);
+
+        let values: Vec<_> = actual[..3]
+            .iter()
+            .map(|tok| tok.string_value(source).unwrap())
+            .collect();
+        assert_eq!(values, vec!["a\"b", "\\", "c\\\""]);
+    }
+
+    #[test]
+    fn interpolated_strings() {
+        let source = r#""a ${ {} } b ${ "c${d}" } e""#;
+        let scanner = Scanner::new(source);
+        let actual = scanner.tokens();
+        let expected = vec![
+            Token::new_string_head(0, "\"a ${"),
+            Token::new_left_brace(6),
+            Token::new_right_brace(7),
+            Token::new_string_middle(9, "} b ${"),
+            Token::new_string_head(16, "\"c${"),
+            Token::new_identifier(20, "d"),
+            Token::new_string_tail(21, "}\""),
+            Token::new_string_tail(24, "} e\""),
+        ];
+        assert_eq!(actual, expected);
+
+        let values: Vec<_> = [&actual[0], &actual[3], &actual[4], &actual[7]]
+            .iter()
+            .map(|tok| tok.string_value(source).unwrap())
+            .collect();
+        assert_eq!(values, vec!["a ", " b ", "c", " e"]);
+    }
+
     #[test]
     fn multiline_string_literals() {
         let source = indoc! {r#""this is a
@@ -398,6 +697,59 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn number_literals_extended() {
+        let source = "0xFF 0b1010 0o17 1_000_000 6.02e23 1e-9 2.5E+2 0x1e-5";
+        let scanner = Scanner::new(source);
+        let tokens = scanner.tokens();
+        let actual: Vec<_> = tokens
+            .iter()
+            .filter(|tok| tok.kind() == TokenKind::Number)
+            .map(|tok| tok.number_value(source))
+            .collect();
+        let expected = vec![255.0, 10.0, 15.0, 1e6, 6.02e23, 1e-9, 250.0, 30.0, 5.0];
+        assert_eq!(actual, expected);
+        // The hex literal's `e` isn't an exponent, so `-` is subtraction.
+        assert_eq!(tokens[tokens.len() - 2].kind(), TokenKind::Minus);
+    }
+
+    #[test]
+    fn number_literals_malformed() {
+        let source = "0x 1e 1__0 1_ 0b102 1e+ 12abc 1.5.2";
+        let scanner = Scanner::new(source);
+        let tokens = scanner.tokens();
+        let actual: Vec<_> = tokens
+            .iter()
+            .map(|tok| (tok.kind(), tok.lexeme(source)))
+            .collect();
+        let expected: Vec<_> = source
+            .split(' ')
+            .map(|lexeme| (TokenKind::InvalidNumber, lexeme))
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        let source = "größe 変数 _ä1 /* é */ x // ü\ny ∞";
+        let scanner = Scanner::new(source);
+        let actual = scanner.tokens();
+        let expected = vec![
+            Token::new_identifier(0, "größe"),
+            Token::new_identifier(8, "変数"),
+            Token::new_identifier(15, "_ä1"),
+            Token::new_block_comment(20, "/* é */"),
+            Token::new_identifier(29, "x"),
+            Token::new_line_comment(31, "// ü"),
+            Token::new_identifier(37, "y"),
+            Token::new_invalid_character(39, "∞"),
+        ];
+        assert_eq!(actual, expected);
+        // The view stops short of a char split by its 7 byte limit.
+        assert_eq!(actual[1].to_string(), "変数");
+        assert_eq!(Token::new_identifier(0, "äääää").to_string(), "äää…");
+    }
+
     #[test]
     fn reserved_keywords() {
         let source =
@@ -406,10 +758,29 @@ mod tests {
         let scanner = Scanner::new(source);
         for (token, word) in scanner.zip(words) {
             assert!(matches!(token.kind(), TokenKind::Keyword(..)));
-            let actual = token.lexeme(&source);
+            let actual = token.lexeme(source);
             let expected = word;
             assert_eq!(actual, expected);
         }
+
+        // Every keyword in the table scans back to itself.
+        for keyword in Keyword::iter() {
+            let mut scanner = Scanner::new(keyword.as_str());
+            let actual = scanner.next().unwrap().kind();
+            assert_eq!(actual, TokenKind::Keyword(keyword));
+        }
+        assert_eq!(Keyword::iter().count(), 16);
+    }
+
+    #[test]
+    fn keyword_lookup() {
+        for &keyword in Keyword::ALL {
+            assert_eq!(Keyword::from_str(keyword.as_str()), Some(keyword));
+        }
+        // Near misses, some with the hash of a keyword.
+        for lexeme in ["fals", "classy", "or_", "whilf", "If", "a", ""] {
+            assert_eq!(Keyword::from_str(lexeme), None, "{lexeme}");
+        }
     }
 
     #[test]
@@ -437,4 +808,79 @@ mod tests {
             assert_eq!(actual, expected);
         }
     }
+
+    /// Apply `edit` to `text`, check relexing gives the tokens of the result,
+    /// and return how many tokens were scanned again.
+    fn relexed(text: &str, edit: Edit) -> usize {
+        let mut source = Source::from(text);
+        let mut tokens = Scanner::new(text).lossless().tokens();
+        source.edit(&edit);
+        let scanned = relex(&mut tokens, &source, &edit);
+        let expected = Scanner::new(source.as_str()).lossless().tokens();
+        assert_eq!(tokens, expected, "{edit:?} of {text:?}");
+        scanned.len()
+    }
+
+    #[test]
+    fn relex_reuses_tokens() {
+        let text = "var a = 1;\nprint a + 2;\nprint a * 3;\n";
+        // `print`, ` ` and `abc`.
+        assert_eq!(relexed(text, Edit::new(17..18, "abc")), 3);
+        // The comment swallowing the first line.
+        assert_eq!(relexed(text, Edit::new(0..0, "// ")), 1);
+        relexed(text, Edit::new(0..text.len(), ""));
+        relexed(text, Edit::new(text.len()..text.len(), "x"));
+        relexed("", Edit::new(0..0, "print 1;"));
+        // `1 .5` becomes `1.5`, a token before the edit.
+        relexed("1 .5", Edit::new(1..2, ""));
+        relexed("a.b", Edit::new(0..1, "1"));
+    }
+
+    #[test]
+    fn relex_comments() {
+        let text = "a /* x /* y */ z */ b\nc // d\ne";
+        // Unbalanced nesting swallows the rest of the source.
+        relexed(text, Edit::new(12..14, ""));
+        relexed(text, Edit::new(0..0, "/*"));
+        relexed(text, Edit::new(7..9, ""));
+        relexed(text, Edit::new(24..24, "/* "));
+        relexed(text, Edit::new(22..23, "/"));
+        // Closing the comment early leaves the rest as code again.
+        let unterminated = "a /* x\nb\nc";
+        assert_eq!(relexed(unterminated, Edit::new(6..6, " */")), 6);
+    }
+
+    #[test]
+    fn relex_strings() {
+        let text = "print \"one\ntwo\";\nprint 3;\nprint \"four\";";
+        relexed(text, Edit::new(6..7, ""));
+        relexed(text, Edit::new(0..0, "\""));
+        relexed(text, Edit::new(14..15, ""));
+        relexed(text, Edit::new(26..26, "\"${"));
+
+        let text = "print \"a ${b + \"${c}\"} d\"; { x; }";
+        relexed(text, Edit::new(18..19, "cc"));
+        relexed(text, Edit::new(14..14, "{"));
+        relexed(text, Edit::new(19..20, ""));
+        relexed(text, Edit::new(21..22, ""));
+        relexed(text, Edit::new(9..11, ""));
+        relexed(text, Edit::new(29..29, "\"${"));
+    }
+
+    #[test]
+    fn relex_everywhere() {
+        let texts = [
+            "var s = \"a ${b + \"${c}\"} d\";\n/* x /* y */ */ print 1.5; // e",
+            "class A { m() { print \"é\n😀\"; } }",
+        ];
+        let insertions = ["\"", "/*", "*/", "//", "${", "{", "}", "\n", "1", ".", "é"];
+        for text in texts {
+            for (i, c) in text.char_indices() {
+                relexed(text, Edit::new(i..i + c.len_utf8(), ""));
+                for insertion in insertions {
+                    relexed(text, Edit::new(i..i, insertion));
+                }
+            }
+        }
+    }
 }
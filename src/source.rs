@@ -1,11 +1,90 @@
+use std::ops::Range;
+use std::rc::Rc;
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// Identifies a [`Source`] in a [`SourceMap`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FileId(pub u32);
+
+/// Every source loaded in a session, like script files or REPL inputs, so
+/// spans from any of them can be mapped back to their text.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<Rc<Source>>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `text`, loaded from `path`, giving it the next [`FileId`]. The path
+    /// may be virtual, like `<repl:1>`.
+    pub fn add(&mut self, path: impl Into<Utf8PathBuf>, text: &str) -> Rc<Source> {
+        let id = FileId(self.files.len() as u32);
+        let source = Rc::new(Source::new(id, path.into(), Box::from(text)));
+        self.files.push(Rc::clone(&source));
+        source
+    }
+
+    /// Returns the number of sources added.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn get(&self, file: FileId) -> Option<&Source> {
+        self.files.get(file.0 as usize).map(Rc::as_ref)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Source {
+    id: FileId,
+    path: Utf8PathBuf,
     buf: Box<str>,
+    /// Byte offset of the start of each line, so positions can be found by
+    /// binary search. The first line starts at 0.
+    line_starts: Vec<u32>,
+}
+
+/// A position in a [`Source`], as a 0-based line and 0-based columns in each
+/// unit a consumer might count in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: u32,
+    /// Column in UTF-8 bytes.
+    pub byte: u32,
+    /// Column in chars, i.e., Unicode scalar values.
+    pub char: u32,
+    /// Column in UTF-16 code units, as editors using the Language Server
+    /// Protocol count.
+    pub utf16: u32,
 }
 
 impl Source {
-    pub fn new(buf: Box<str>) -> Self {
-        Self { buf }
+    pub fn new(id: FileId, path: Utf8PathBuf, buf: Box<str>) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(buf.match_indices('\n').map(|(i, _)| i as u32 + 1))
+            .collect();
+        Self {
+            id,
+            path,
+            buf,
+            line_starts,
+        }
+    }
+
+    pub fn id(&self) -> FileId {
+        self.id
+    }
+
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
     }
 
     pub fn as_str(&self) -> &str {
@@ -15,16 +94,155 @@ impl Source {
     pub fn span_to_lexeme(&self, span: &Span) -> Option<&str> {
         self.buf.get((span.start as usize)..(span.end as usize))
     }
+
+    /// Returns the number of lines. An empty source has one empty line.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the position of byte `offset`. Offsets past the end are
+    /// clamped to it, and offsets inside a char round down to its start.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let offset = self.buf.floor_char_boundary(offset);
+        let line = self
+            .line_starts
+            .partition_point(|&start| start as usize <= offset)
+            - 1;
+        let line_start = self.line_starts[line] as usize;
+        let prefix = &self.buf[line_start..offset];
+        LineCol {
+            line: line as u32,
+            byte: prefix.len() as u32,
+            char: prefix.chars().count() as u32,
+            utf16: prefix.chars().map(char::len_utf16).sum::<usize>() as u32,
+        }
+    }
+
+    /// Returns the byte offset of `utf16` code units into 0-based `line`, the
+    /// inverse of [`LineCol::utf16`]. Lines past the end are clamped to the
+    /// end of the source, columns past the end of a line to the end of the
+    /// line, and columns inside a char round down to its start.
+    pub fn utf16_offset(&self, line: usize, utf16: usize) -> usize {
+        let Some(text) = self.line_text(line) else {
+            return self.buf.len();
+        };
+        let line_start = self.line_starts[line] as usize;
+        let mut units = 0;
+        for (i, c) in text.char_indices() {
+            units += c.len_utf16();
+            if units > utf16 {
+                return line_start + i;
+            }
+        }
+        line_start + text.len()
+    }
+
+    /// Returns the start and end positions of `span`.
+    pub fn span_to_range(&self, span: &Span) -> Range<LineCol> {
+        self.line_col(span.start as usize)..self.line_col(span.end as usize)
+    }
+
+    /// Returns the text of 0-based `line`, without its line terminator.
+    pub fn line_text(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line)? as usize;
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .map_or(self.buf.len(), |&next| next as usize - 1);
+        let text = &self.buf[start..end];
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    /// Apply `edit` to the text, as an editor changed it.
+    ///
+    /// Panics if the edit's range is out of bounds or not on char boundaries.
+    pub fn edit(&mut self, edit: &Edit) {
+        let Range { start, end } = edit.range;
+        let mut buf = String::from(std::mem::take(&mut self.buf));
+        buf.replace_range(start..end, &edit.text);
+        self.buf = buf.into_boxed_str();
+
+        // Lines starting in the replaced range lost their newline. Those after
+        // it move with the text.
+        let first = self
+            .line_starts
+            .partition_point(|&line_start| line_start as usize <= start);
+        let last = self
+            .line_starts
+            .partition_point(|&line_start| line_start as usize <= end);
+        let moved: Vec<_> = self.line_starts[last..]
+            .iter()
+            .map(|&line_start| (line_start as isize + edit.delta()) as u32)
+            .collect();
+        self.line_starts.truncate(first);
+        self.line_starts.extend(
+            edit.text
+                .match_indices('\n')
+                .map(|(i, _)| (start + i + 1) as u32),
+        );
+        self.line_starts.extend(moved);
+    }
+}
+
+/// A change to a [`Source`]: the bytes in `range` replaced with `text`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl Edit {
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Self {
+        Self {
+            range,
+            text: text.into(),
+        }
+    }
+
+    /// How many bytes longer the source is after the edit.
+    pub fn delta(&self) -> isize {
+        self.text.len() as isize - self.range.len() as isize
+    }
+
+    /// Returns where `offset` ends up after the edit. Offsets after the
+    /// replaced range move with the text, and those inside it move to the end
+    /// of the new text.
+    pub fn map_offset(&self, offset: u32) -> u32 {
+        let offset = offset as usize;
+        if offset <= self.range.start {
+            offset as u32
+        } else if offset < self.range.end {
+            (self.range.start + self.text.len()) as u32
+        } else {
+            (offset as isize + self.delta()) as u32
+        }
+    }
+
+    /// Returns where `span` ends up after the edit, see [`Edit::map_offset`].
+    pub fn map_span(&self, span: &Span) -> Span {
+        Span {
+            file: span.file,
+            start: self.map_offset(span.start),
+            end: self.map_offset(span.end),
+        }
+    }
 }
 
+/// A standalone source, as the first and only file of a session.
 impl From<&str> for Source {
     fn from(value: &str) -> Self {
-        Self::new(Box::from(value))
+        Self::new(
+            FileId::default(),
+            Utf8PathBuf::from("<input>"),
+            Box::from(value),
+        )
     }
 }
 
+/// A range of bytes in a file.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Span {
+    pub file: FileId,
     pub start: u32,
     pub end: u32,
 }
@@ -48,11 +266,9 @@ where
     /// The column is 0 immediately after a newline character has been read.
     column: usize,
 
-    /// Byte offset of the start of the current line. This is the sum of lengths
-    /// of all previous lines. Keeping track of things this way allows efficient
-    /// computation of the current line, column, and byte offset while only
-    /// updating one of the counters in `next()` in the common case.
-    start_of_line: usize,
+    /// Byte offset just past the last character read. Characters may be more
+    /// than one byte, so this isn't derived from the line and column.
+    offset: usize,
 
     /// Peeked character.
     peeked: Option<char>,
@@ -67,7 +283,7 @@ where
             iter,
             line: 1,
             column: 0,
-            start_of_line: 0,
+            offset: 0,
             peeked: None,
         }
     }
@@ -88,9 +304,9 @@ where
         self.column
     }
 
-    /// Offset is the iterator's character index + 1
+    /// Byte offset just past the last character read.
     pub fn offset(&self) -> usize {
-        self.start_of_line + self.column
+        self.offset
     }
 }
 
@@ -101,9 +317,10 @@ where
     type Item = char;
 
     fn next(&mut self) -> Option<char> {
-        match self.peeked.take().or_else(|| self.iter.next())? {
+        let c = self.peeked.take().or_else(|| self.iter.next())?;
+        self.offset += c.len_utf8();
+        match c {
             '\n' => {
-                self.start_of_line += self.column + 1;
                 self.line += 1;
                 self.column = 0;
                 Some('\n')
@@ -115,3 +332,112 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col() {
+        // `é` is 2 bytes, `😀` is 4 bytes and 2 UTF-16 code units.
+        let source = Source::from("ab\r\né😀x\n\nend");
+        let at = |line, byte, char, utf16| LineCol {
+            line,
+            byte,
+            char,
+            utf16,
+        };
+        assert_eq!(source.line_col(0), at(0, 0, 0, 0));
+        assert_eq!(source.line_col(4), at(1, 0, 0, 0));
+        assert_eq!(source.line_col(10), at(1, 6, 2, 3));
+        // Inside `😀`, rounds down to its start.
+        assert_eq!(source.line_col(8), at(1, 2, 1, 1));
+        assert_eq!(source.line_col(12), at(2, 0, 0, 0));
+        assert_eq!(source.line_col(100), at(3, 3, 3, 3));
+
+        let span = Span {
+            file: FileId(0),
+            start: 6,
+            end: 11,
+        };
+        assert_eq!(source.span_to_range(&span), at(1, 2, 1, 1)..at(1, 7, 3, 4));
+    }
+
+    #[test]
+    fn utf16_offset() {
+        let source = Source::from("ab\r\né😀x\n\nend");
+        assert_eq!(source.utf16_offset(0, 1), 1);
+        assert_eq!(source.utf16_offset(1, 1), 6);
+        assert_eq!(source.utf16_offset(1, 3), 10);
+        // Inside `😀`, rounds down to its start.
+        assert_eq!(source.utf16_offset(1, 2), 6);
+        // Past the end of a line, before its `\r\n`.
+        assert_eq!(source.utf16_offset(0, 10), 2);
+        assert_eq!(source.utf16_offset(3, 3), 16);
+        assert_eq!(source.utf16_offset(9, 0), 16);
+        for offset in [0, 2, 4, 6, 10, 11, 12, 13, 16] {
+            let line_col = source.line_col(offset);
+            assert_eq!(
+                source.utf16_offset(line_col.line as usize, line_col.utf16 as usize),
+                offset
+            );
+        }
+    }
+
+    #[test]
+    fn line_text() {
+        let source = Source::from("ab\r\né😀x\n\nend");
+        assert_eq!(source.line_count(), 4);
+        let lines: Vec<_> = (0..5).map(|line| source.line_text(line)).collect();
+        assert_eq!(
+            lines,
+            vec![Some("ab"), Some("é😀x"), Some(""), Some("end"), None]
+        );
+    }
+
+    #[test]
+    fn edit() {
+        let mut source = Source::from("a\nbc\nd\n\ne");
+        let edits = [
+            Edit::new(1..3, ""),
+            Edit::new(0..0, "x\ny\n"),
+            Edit::new(5..7, "\n\n"),
+            Edit::new(8..8, "😀"),
+            Edit::new(0..15, "z"),
+        ];
+        for edit in &edits {
+            source.edit(edit);
+            let expected = Source::from(source.as_str());
+            assert_eq!(source.line_starts, expected.line_starts, "{edit:?}");
+        }
+        assert_eq!(source.as_str(), "z");
+    }
+
+    #[test]
+    fn map_span() {
+        let edit = Edit::new(4..6, "xyz");
+        let span = |start, end| Span {
+            file: FileId::default(),
+            start,
+            end,
+        };
+        assert_eq!(edit.map_span(&span(0, 4)), span(0, 4));
+        assert_eq!(edit.map_span(&span(5, 8)), span(7, 9));
+        assert_eq!(edit.map_span(&span(6, 8)), span(7, 9));
+        assert_eq!(edit.map_span(&span(2, 10)), span(2, 11));
+    }
+
+    #[test]
+    fn source_map() {
+        let mut sources = SourceMap::new();
+        let first = sources.add("a.lox", "print 1;");
+        let second = sources.add("<repl:2>", "print 2;");
+        assert_eq!((first.id(), second.id()), (FileId(0), FileId(1)));
+        assert_eq!(sources.len(), 2);
+
+        let source = sources.get(FileId(1)).unwrap();
+        assert_eq!(source.path(), "<repl:2>");
+        assert_eq!(source.as_str(), "print 2;");
+        assert!(sources.get(FileId(2)).is_none());
+    }
+}
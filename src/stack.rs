@@ -0,0 +1,15 @@
+//! Room on the stack for recursion as deep as the code being processed.
+
+/// How much stack a recursive step needs left before it moves to a new
+/// segment of [`STACK_SEGMENT`] bytes. A debug build can take tens of
+/// kilobytes of stack per Lox call.
+const RED_ZONE: usize = 1024 * 1024;
+const STACK_SEGMENT: usize = 8 * 1024 * 1024;
+
+/// Run `f`, on a new segment of stack if this one is nearly used up. Each
+/// pass recursing over code, like parsing or interpreting it, grows the stack
+/// through this, so code nested as deeply as the parser allows fits on any
+/// thread, even in a debug build.
+pub(crate) fn grow<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, f)
+}
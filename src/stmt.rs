@@ -0,0 +1,136 @@
+use std::fmt;
+
+use crate::expr::Expr;
+use crate::source::Span;
+use crate::token::Token;
+
+#[derive(Debug, PartialEq)]
+pub enum Stmt<'a> {
+    Block {
+        statements: Vec<Stmt<'a>>,
+    },
+    Class {
+        name: &'a Token,
+        /// An [`Expr::Variable`] naming the superclass.
+        superclass: Option<Box<Expr<'a>>>,
+        methods: Vec<FunctionDecl<'a>>,
+    },
+    /// Where a declaration couldn't be parsed, covering the tokens skipped.
+    Error {
+        span: Span,
+    },
+    Expression {
+        expression: Box<Expr<'a>>,
+    },
+    Function {
+        declaration: FunctionDecl<'a>,
+    },
+    If {
+        condition: Box<Expr<'a>>,
+        then_branch: Box<Stmt<'a>>,
+        else_branch: Option<Box<Stmt<'a>>>,
+    },
+    Print {
+        expression: Box<Expr<'a>>,
+    },
+    Return {
+        keyword: &'a Token,
+        value: Option<Box<Expr<'a>>>,
+    },
+    Var {
+        name: &'a Token,
+        initializer: Option<Box<Expr<'a>>>,
+    },
+    While {
+        condition: Box<Expr<'a>>,
+        body: Box<Stmt<'a>>,
+    },
+}
+
+/// A function declaration, like `fun add(a, b) { return a + b; }`.
+#[derive(Debug, PartialEq)]
+pub struct FunctionDecl<'a> {
+    pub name: &'a Token,
+    pub params: Vec<&'a Token>,
+    pub body: Vec<Stmt<'a>>,
+}
+
+/// Display Stmt in Polish notation.
+///
+/// E.g., "print 1 + 2;" -> "(print (+ 1 2))"
+impl fmt::Display for Stmt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self {
+            Stmt::Block { statements } => {
+                f.write_str("(block")?;
+                for statement in statements {
+                    write!(f, " {statement}")?;
+                }
+                f.write_str(")")
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                write!(f, "(class {name}")?;
+                if let Some(superclass) = superclass {
+                    write!(f, " < {superclass}")?;
+                }
+                for method in methods {
+                    write!(f, " ({method})")?;
+                }
+                f.write_str(")")
+            }
+            Stmt::Error { .. } => f.write_str("(error)"),
+            Stmt::Expression { expression } => write!(f, "(; {expression})"),
+            Stmt::Function { declaration } => write!(f, "(fun {declaration})"),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch: Some(else_branch),
+            } => write!(f, "(if {condition} {then_branch} {else_branch})"),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch: None,
+            } => write!(f, "(if {condition} {then_branch})"),
+            Stmt::Print { expression } => write!(f, "(print {expression})"),
+            Stmt::Return {
+                keyword,
+                value: Some(value),
+            } => write!(f, "({keyword} {value})"),
+            Stmt::Return {
+                keyword,
+                value: None,
+            } => write!(f, "({keyword})"),
+            Stmt::Var {
+                name,
+                initializer: Some(initializer),
+            } => write!(f, "(var {name} {initializer})"),
+            Stmt::Var {
+                name,
+                initializer: None,
+            } => write!(f, "(var {name})"),
+            Stmt::While { condition, body } => write!(f, "(while {condition} {body})"),
+        }
+    }
+}
+
+/// Display a function's name, parameters and body, e.g., "f (a b) (print a)".
+impl fmt::Display for FunctionDecl<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (", self.name)?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{param}")?;
+        }
+        f.write_str(")")?;
+        for statement in &self.body {
+            write!(f, " {statement}")?;
+        }
+        Ok(())
+    }
+}
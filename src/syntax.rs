@@ -0,0 +1,392 @@
+//! A lossless concrete syntax tree, for tools like formatters that need the
+//! source exactly as written.
+//!
+//! The tree is in two layers. Green nodes and tokens are immutable and know
+//! only their kind, text and children, so unchanged subtrees can be shared.
+//! Red [`SyntaxNode`]s and [`SyntaxToken`]s are built over them on demand,
+//! and add positions and parents. Whitespace and comments are [`Trivia`] of
+//! the token before or after them, so writing a tree out reproduces its
+//! source byte for byte. The [`ast`] module gives a typed view of the tree.
+
+use std::fmt;
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::source::{Edit, FileId};
+use crate::token::{Token, TokenKind};
+
+pub mod ast;
+mod green;
+mod parser;
+mod red;
+mod reparse;
+
+use ast::AstNode;
+pub use green::{Checkpoint, GreenBuilder, GreenElement, GreenNode, GreenToken, Trivia};
+pub use red::{SyntaxElement, SyntaxNode, SyntaxToken};
+
+/// The kind of a [`SyntaxNode`]. Tokens are kinded by
+/// [`TokenKind`](crate::token::TokenKind).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    /// A whole file, ending with an [`Eof`](crate::token::TokenKind::Eof)
+    /// token.
+    Program,
+    // Declarations and statements.
+    ClassDecl,
+    FunDecl,
+    /// A function or method, after any `fun` keyword.
+    Function,
+    /// The parenthesized parameters of a function.
+    ParamList,
+    VarDecl,
+    ExprStmt,
+    ForStmt,
+    IfStmt,
+    PrintStmt,
+    ReturnStmt,
+    WhileStmt,
+    Block,
+    // Expressions.
+    Assign,
+    Binary,
+    Call,
+    /// The parenthesized arguments of a call.
+    ArgList,
+    Get,
+    Grouping,
+    Interpolation,
+    Literal,
+    Logical,
+    Super,
+    This,
+    Unary,
+    Variable,
+    /// Tokens that couldn't be parsed, or nothing where a node was missing.
+    Error,
+}
+
+/// The result of [`parse`]: a syntax tree covering the whole source, and the
+/// errors found building it.
+#[derive(Debug, Clone)]
+pub struct Parse {
+    green: Rc<GreenNode>,
+    file: FileId,
+    errors: Vec<Error>,
+}
+
+impl Parse {
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.green
+    }
+
+    /// Returns the root of the tree, a [`SyntaxKind::Program`] node.
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(Rc::clone(&self.green), self.file)
+    }
+
+    /// Returns the typed view of the tree.
+    pub fn tree(&self) -> ast::Program {
+        ast::Program::cast(self.syntax()).expect("the root is a program")
+    }
+
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    /// Returns the tokens of the tree, without trivia or the final `Eof`, for
+    /// a [`Parser`](crate::parser::Parser) to lower the tree with.
+    pub fn tokens(&self) -> Vec<Token> {
+        self.syntax()
+            .tokens()
+            .filter(|token| token.kind() != TokenKind::Eof)
+            .map(|token| {
+                Token::new(token.kind(), token.span().start as usize, token.text())
+                    .in_file(self.file)
+            })
+            .collect()
+    }
+
+    /// Returns the tree of `source`, made by applying `edit` to the source of
+    /// this tree. When the edit stays inside a token or a block, only that is
+    /// parsed again, and the rest of the tree is shared with this one.
+    pub fn reparse(&self, source: &str, edit: &Edit) -> Parse {
+        reparse::reparse(self, source, edit)
+    }
+}
+
+/// Writes the source back, exactly as parsed.
+impl fmt::Display for Parse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.green.fmt(f)
+    }
+}
+
+/// Parse `source` into a lossless syntax tree. Never fails: tokens that don't
+/// fit the grammar are kept in [`SyntaxKind::Error`] nodes.
+pub fn parse(source: &str) -> Parse {
+    parse_with_file(source, FileId::default())
+}
+
+/// Parse `source`, loaded as `file` of a [`SourceMap`].
+///
+/// [`SourceMap`]: crate::source::SourceMap
+pub fn parse_with_file(source: &str, file: FileId) -> Parse {
+    let (green, errors) = parser::parse(source, file);
+    Parse {
+        green,
+        file,
+        errors,
+    }
+}
+
+/// Parse `source`, loaded as `file`, as a single expression, like a line of
+/// a REPL to print the value of. The root is still a program node, holding
+/// just the expression and any unexpected tokens after it.
+pub fn parse_expression(source: &str, file: FileId) -> Parse {
+    let (green, errors) = parser::parse_expression(source, file);
+    Parse {
+        green,
+        file,
+        errors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use camino::Utf8Path;
+    use indoc::indoc;
+
+    use super::*;
+
+    /// Sources with every kind of token, trivia and error.
+    const SNIPPETS: &[&str] = &[
+        "",
+        "  \n\t",
+        "// only a comment",
+        "print 1;\r\nprint 2; /* two */\r\n",
+        "var s = \"a ${b + \"${c}\"} d\";",
+        "class A < B { m() { return super.m(); } }",
+        "for (;;) {}",
+        "var = 1;\nprint (1;\nfun (a b) {",
+        "print \"unterminated\n",
+        "x = 1 @ 2 # 3;",
+        "a.b = 1_0__0 + 0x;",
+        "} ) ; else",
+        "/* unterminated /* nested */",
+        "print \"${a\";",
+    ];
+
+    #[test]
+    fn round_trip() {
+        let examples = Utf8Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let mut sources: Vec<String> = SNIPPETS.iter().map(|s| s.to_string()).collect();
+        for entry in fs::read_dir(examples).unwrap() {
+            sources.push(fs::read_to_string(entry.unwrap().path()).unwrap());
+        }
+        for source in &sources {
+            let parse = parse(source);
+            assert_eq!(parse.to_string(), *source);
+            for token in parse.syntax().tokens() {
+                let span = token.span();
+                assert_eq!(
+                    &source[span.start as usize..span.end as usize],
+                    token.text()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn trivia() {
+        let source = "var a = 1; // one\n\n// two\nprint a;\n";
+        let tokens: Vec<_> = parse(source).syntax().tokens().collect();
+        let texts = |trivia: &[Trivia]| -> Vec<String> {
+            trivia
+                .iter()
+                .map(|trivia| trivia.text().to_owned())
+                .collect()
+        };
+
+        let semicolon = &tokens[4];
+        assert_eq!(semicolon.text(), ";");
+        assert_eq!(texts(semicolon.trailing()), [" ", "// one", "\n"]);
+        let print = &tokens[5];
+        assert_eq!(
+            print.kind(),
+            TokenKind::Keyword(crate::token::Keyword::Print)
+        );
+        assert_eq!(texts(print.leading()), ["\n", "// two", "\n"]);
+        assert_eq!(print.full_span().start, 18);
+        assert_eq!(print.span().start, 26);
+
+        let eof = tokens.last().unwrap();
+        assert_eq!(eof.kind(), TokenKind::Eof);
+        assert!(eof.leading().is_empty());
+    }
+
+    #[test]
+    fn tree() {
+        let parse = parse("print -a + 2; // sum\n");
+        assert!(parse.errors().is_empty());
+        let expected = indoc! {r#"
+            Program@0..21
+              PrintStmt@0..13
+                Keyword(Print)@0..5 "print"
+                Binary@6..12
+                  Unary@6..8
+                    Minus@6..7 "-"
+                    Variable@7..8
+                      Identifier@7..8 "a"
+                  Plus@9..10 "+"
+                  Literal@11..12
+                    Number@11..12 "2"
+                Semicolon@12..13 ";"
+              Eof@21..21 ""
+        "#};
+        assert_eq!(format!("{:?}", parse.syntax()), expected);
+    }
+
+    #[test]
+    fn typed_view() {
+        let source = indoc! {"
+            class Square < Shape {
+              init(side) { this.side = side; }
+              area() { return this.side * this.side; }
+            }
+            for (var i = 0; i < 3; i = i + 1) print i;
+            for (;;) {}
+        "};
+        let parse = parse(source);
+        assert!(parse.errors().is_empty());
+        let statements: Vec<_> = parse.tree().statements().collect();
+        assert_eq!(statements.len(), 3);
+
+        let ast::Stmt::Class(class) = &statements[0] else {
+            panic!("expected a class, got {:?}", statements[0]);
+        };
+        assert_eq!(class.name().unwrap().text(), "Square");
+        assert_eq!(class.superclass().unwrap().text(), "Shape");
+        let methods: Vec<_> = class
+            .methods()
+            .map(|method| method.name().unwrap().text().to_owned())
+            .collect();
+        assert_eq!(methods, ["init", "area"]);
+        let init = class.methods().next().unwrap();
+        let params: Vec<_> = init.params().unwrap().params().collect();
+        assert_eq!(params.len(), 1);
+        let body: Vec<_> = init.body().unwrap().statements().collect();
+        let ast::Stmt::Expression(assign) = &body[0] else {
+            panic!("expected an expression, got {:?}", body[0]);
+        };
+        assert!(matches!(
+            assign.expression(),
+            Some(ast::Expr::Assign(assign)) if matches!(assign.target(), Some(ast::Expr::Get(_)))
+        ));
+
+        let ast::Stmt::For(for_loop) = &statements[1] else {
+            panic!("expected a for loop, got {:?}", statements[1]);
+        };
+        assert!(matches!(for_loop.initializer(), Some(ast::Stmt::Var(_))));
+        assert_eq!(for_loop.condition().unwrap().syntax().to_string(), "i < 3");
+        assert_eq!(
+            for_loop.increment().unwrap().syntax().to_string(),
+            "i = i + 1"
+        );
+        assert!(matches!(for_loop.body(), Some(ast::Stmt::Print(_))));
+
+        let ast::Stmt::For(forever) = &statements[2] else {
+            panic!("expected a for loop, got {:?}", statements[2]);
+        };
+        assert!(forever.initializer().is_none());
+        assert!(forever.condition().is_none());
+        assert!(forever.increment().is_none());
+        assert!(matches!(forever.body(), Some(ast::Stmt::Block(_))));
+    }
+
+    #[test]
+    fn errors() {
+        let parse = parse("var = 1;\nprint (1;\n1 = 2;");
+        let errors: Vec<_> = parse.errors().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            [
+                "expected variable name",
+                "unclosed parenthesis",
+                "invalid assignment target"
+            ]
+        );
+        // The statements are all still there.
+        assert_eq!(parse.tree().statements().count(), 3);
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        let source = r#"print "\q"; print "ok\u{110000}"; print "\u41${a}\z";"#;
+        let spans: Vec<_> = parse(source)
+            .errors()
+            .iter()
+            .map(|error| {
+                assert!(matches!(error, Error::InvalidEscape { .. }), "{error:?}");
+                error.span().start..error.span().end
+            })
+            .collect();
+        assert_eq!(spans, [7..9, 21..31, 41..43, 49..51]);
+    }
+
+    #[test]
+    fn recovery() {
+        let sources = [
+            (
+                "var a = ;\nprint a\nfun f() {\n  1 +;\n  return 2;\n}\nf(1, 2) = 3;\nprint 4;",
+                &[
+                    "parse error",
+                    "expected ';' after value",
+                    "parse error",
+                    "invalid assignment target",
+                ][..],
+            ),
+            ("{ print 1 } print 2;", &["expected ';' after value"]),
+            ("} print 1;", &["parse error"]),
+            (
+                "var a = 1 2 3; print a;",
+                &["expected ';' after variable declaration"],
+            ),
+            ("class A print 1;", &["expected '{' before class body"]),
+            ("print \"abc", &["unterminated string"]),
+            ("/* abc", &["unterminated comment"]),
+            ("print @; print 1;", &["invalid character"]),
+        ];
+        for (source, expected) in sources {
+            let errors: Vec<_> = parse(source)
+                .errors()
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            assert_eq!(errors, expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let nested = |open: &str, middle: &str, close: &str, n: usize| {
+            format!("{}{middle}{}", open.repeat(n), close.repeat(n))
+        };
+        for (n, expected) in [(500, &[][..]), (5000, &["E0032"][..])] {
+            let sources = [
+                nested("print ", &nested("(", "1", ")", n), ";", 1),
+                nested("print ", &nested("-", "1", "", n), ";", 1),
+                nested("{", "print 1;", "}", n),
+                nested("if (true) ", "print 1;", "", n),
+                nested("print 1", &"+ 1".repeat(n), ";", 1),
+                nested("f", &"()".repeat(n), ";", 1),
+            ];
+            for source in sources {
+                let errors: Vec<_> = parse(&source).errors().iter().map(Error::code).collect();
+                assert_eq!(errors, expected, "{}", &source[..20]);
+            }
+        }
+    }
+}
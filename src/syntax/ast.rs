@@ -0,0 +1,425 @@
+//! A typed view of the syntax tree. Each type wraps a [`SyntaxNode`] of one
+//! kind, with accessors for its parts. Parts are optional, since the tree
+//! keeps code with errors in it.
+
+use crate::syntax::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+use crate::token::{Keyword, TokenKind};
+
+/// A typed wrapper of a [`SyntaxNode`].
+pub trait AstNode: Sized {
+    /// Wrap `node`, or return `None` if it's the wrong kind.
+    fn cast(node: SyntaxNode) -> Option<Self>;
+
+    fn syntax(&self) -> &SyntaxNode;
+}
+
+/// Define a wrapper for each node kind of the same name.
+macro_rules! ast_nodes {
+    ($($(#[$meta:meta])* $name:ident,)*) => {$(
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct $name(SyntaxNode);
+
+        impl AstNode for $name {
+            fn cast(node: SyntaxNode) -> Option<Self> {
+                (node.kind() == SyntaxKind::$name).then_some(Self(node))
+            }
+
+            fn syntax(&self) -> &SyntaxNode {
+                &self.0
+            }
+        }
+    )*};
+}
+
+/// Define an enum of wrappers, cast from any of their kinds.
+macro_rules! ast_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident($node:ident),)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum $name {
+            $($variant($node),)*
+        }
+
+        impl AstNode for $name {
+            fn cast(node: SyntaxNode) -> Option<Self> {
+                match node.kind() {
+                    $(SyntaxKind::$node => Some($name::$variant($node(node))),)*
+                    _ => None,
+                }
+            }
+
+            fn syntax(&self) -> &SyntaxNode {
+                match self {
+                    $($name::$variant(node) => node.syntax(),)*
+                }
+            }
+        }
+    };
+}
+
+ast_nodes! {
+    Program,
+    ClassDecl,
+    FunDecl,
+    Function,
+    ParamList,
+    VarDecl,
+    ExprStmt,
+    ForStmt,
+    IfStmt,
+    PrintStmt,
+    ReturnStmt,
+    WhileStmt,
+    Block,
+    Assign,
+    Binary,
+    Call,
+    ArgList,
+    Get,
+    Grouping,
+    Interpolation,
+    Literal,
+    Logical,
+    Super,
+    This,
+    Unary,
+    Variable,
+}
+
+ast_enum! {
+    /// A declaration or statement.
+    Stmt {
+        Class(ClassDecl),
+        Fun(FunDecl),
+        Var(VarDecl),
+        Expression(ExprStmt),
+        For(ForStmt),
+        If(IfStmt),
+        Print(PrintStmt),
+        Return(ReturnStmt),
+        While(WhileStmt),
+        Block(Block),
+    }
+}
+
+ast_enum! {
+    Expr {
+        Assign(Assign),
+        Binary(Binary),
+        Call(Call),
+        Get(Get),
+        Grouping(Grouping),
+        Interpolation(Interpolation),
+        Literal(Literal),
+        Logical(Logical),
+        Super(Super),
+        This(This),
+        Unary(Unary),
+        Variable(Variable),
+    }
+}
+
+/// Returns the first child of `parent` that casts to `N`.
+fn child<N: AstNode>(parent: &SyntaxNode) -> Option<N> {
+    parent.children().find_map(N::cast)
+}
+
+/// Returns the children of `parent` that cast to `N`.
+fn children<N: AstNode>(parent: &SyntaxNode) -> impl Iterator<Item = N> + use<N> {
+    parent.children().filter_map(N::cast)
+}
+
+/// Returns the tokens directly in `parent`, not in its child nodes.
+fn tokens(parent: &SyntaxNode) -> impl Iterator<Item = SyntaxToken> + use<> {
+    parent
+        .children_with_tokens()
+        .filter_map(SyntaxElement::into_token)
+}
+
+/// Returns the first token of `kind` directly in `parent`.
+fn token(parent: &SyntaxNode, kind: TokenKind) -> Option<SyntaxToken> {
+    tokens(parent).find(|token| token.kind() == kind)
+}
+
+impl Program {
+    pub fn statements(&self) -> impl Iterator<Item = Stmt> + use<> {
+        children(&self.0)
+    }
+}
+
+impl ClassDecl {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Identifier)
+    }
+
+    /// The name after `<`, if the class has a superclass.
+    pub fn superclass(&self) -> Option<SyntaxToken> {
+        tokens(&self.0)
+            .skip_while(|token| token.kind() != TokenKind::Less)
+            .find(|token| token.kind() == TokenKind::Identifier)
+    }
+
+    pub fn methods(&self) -> impl Iterator<Item = Function> + use<> {
+        children(&self.0)
+    }
+}
+
+impl FunDecl {
+    pub fn function(&self) -> Option<Function> {
+        child(&self.0)
+    }
+}
+
+impl Function {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Identifier)
+    }
+
+    pub fn params(&self) -> Option<ParamList> {
+        child(&self.0)
+    }
+
+    pub fn body(&self) -> Option<Block> {
+        child(&self.0)
+    }
+}
+
+impl ParamList {
+    /// The parameter names.
+    pub fn params(&self) -> impl Iterator<Item = SyntaxToken> + use<> {
+        tokens(&self.0).filter(|token| token.kind() == TokenKind::Identifier)
+    }
+}
+
+impl VarDecl {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Identifier)
+    }
+
+    pub fn initializer(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl ExprStmt {
+    pub fn expression(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl ForStmt {
+    /// The children between `(` and `)`, and those after.
+    fn clauses(&self) -> (Vec<SyntaxElement>, Vec<SyntaxElement>) {
+        let mut elements: Vec<_> = self.0.children_with_tokens().collect();
+        let close = elements.iter().position(|element| {
+            matches!(element, SyntaxElement::Token(token) if token.kind() == TokenKind::RightParen)
+        });
+        let body = elements.split_off(close.unwrap_or(elements.len()));
+        (elements, body)
+    }
+
+    /// The condition and increment clauses, split at the `;` ending the
+    /// condition. The initializer ends with its own `;`, if it has one.
+    fn expression_clauses(&self) -> (Option<Expr>, Option<Expr>) {
+        let (clauses, _) = self.clauses();
+        let semicolon = clauses.iter().rposition(|element| {
+            matches!(element, SyntaxElement::Token(token) if token.kind() == TokenKind::Semicolon)
+        });
+        let (condition, increment) = clauses.split_at(semicolon.unwrap_or(clauses.len()));
+        let expr = |elements: &[SyntaxElement]| {
+            elements
+                .iter()
+                .find_map(|element| Expr::cast(element.clone().into_node()?))
+        };
+        (expr(condition), expr(increment))
+    }
+
+    pub fn initializer(&self) -> Option<Stmt> {
+        let (clauses, _) = self.clauses();
+        clauses
+            .into_iter()
+            .find_map(|element| Stmt::cast(element.into_node()?))
+    }
+
+    pub fn condition(&self) -> Option<Expr> {
+        self.expression_clauses().0
+    }
+
+    pub fn increment(&self) -> Option<Expr> {
+        self.expression_clauses().1
+    }
+
+    pub fn body(&self) -> Option<Stmt> {
+        let (_, body) = self.clauses();
+        body.into_iter()
+            .find_map(|element| Stmt::cast(element.into_node()?))
+    }
+}
+
+impl IfStmt {
+    pub fn condition(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn then_branch(&self) -> Option<Stmt> {
+        child(&self.0)
+    }
+
+    pub fn else_branch(&self) -> Option<Stmt> {
+        children(&self.0).nth(1)
+    }
+}
+
+impl PrintStmt {
+    pub fn expression(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl ReturnStmt {
+    pub fn keyword(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Keyword(Keyword::Return))
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl WhileStmt {
+    pub fn condition(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn body(&self) -> Option<Stmt> {
+        child(&self.0)
+    }
+}
+
+impl Block {
+    pub fn statements(&self) -> impl Iterator<Item = Stmt> + use<> {
+        children(&self.0)
+    }
+}
+
+impl Assign {
+    /// The variable or property assigned to.
+    pub fn target(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        children(&self.0).nth(1)
+    }
+}
+
+impl Binary {
+    pub fn left(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn operator(&self) -> Option<SyntaxToken> {
+        tokens(&self.0).next()
+    }
+
+    pub fn right(&self) -> Option<Expr> {
+        children(&self.0).nth(1)
+    }
+}
+
+impl Logical {
+    pub fn left(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn operator(&self) -> Option<SyntaxToken> {
+        tokens(&self.0).next()
+    }
+
+    pub fn right(&self) -> Option<Expr> {
+        children(&self.0).nth(1)
+    }
+}
+
+impl Unary {
+    pub fn operator(&self) -> Option<SyntaxToken> {
+        tokens(&self.0).next()
+    }
+
+    pub fn operand(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl Call {
+    pub fn callee(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn arguments(&self) -> Option<ArgList> {
+        child(&self.0)
+    }
+}
+
+impl ArgList {
+    pub fn arguments(&self) -> impl Iterator<Item = Expr> + use<> {
+        children(&self.0)
+    }
+}
+
+impl Get {
+    pub fn object(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Identifier)
+    }
+}
+
+impl Grouping {
+    pub fn expression(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl Interpolation {
+    /// The string segments around the holes, from the head to the tail.
+    pub fn segments(&self) -> impl Iterator<Item = SyntaxToken> + use<> {
+        tokens(&self.0)
+    }
+
+    /// The expressions in the holes.
+    pub fn holes(&self) -> impl Iterator<Item = Expr> + use<> {
+        children(&self.0)
+    }
+}
+
+impl Literal {
+    pub fn token(&self) -> Option<SyntaxToken> {
+        tokens(&self.0).next()
+    }
+}
+
+impl Super {
+    pub fn keyword(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Keyword(Keyword::Super))
+    }
+
+    pub fn method(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Identifier)
+    }
+}
+
+impl This {
+    pub fn keyword(&self) -> Option<SyntaxToken> {
+        tokens(&self.0).next()
+    }
+}
+
+impl Variable {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        tokens(&self.0).next()
+    }
+}
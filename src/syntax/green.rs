@@ -0,0 +1,259 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::syntax::SyntaxKind;
+use crate::token::TokenKind;
+
+/// Whitespace or a comment, attached to the token before or after it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    kind: TokenKind,
+    text: Box<str>,
+}
+
+impl Trivia {
+    pub fn new(kind: TokenKind, text: &str) -> Self {
+        debug_assert!(kind.is_trivia(), "{kind:?} is not trivia");
+        Self {
+            kind,
+            text: Box::from(text),
+        }
+    }
+
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// An immutable token with its trivia. It doesn't know its position, so
+/// equal tokens can be shared, within a tree or between versions of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreenToken {
+    kind: TokenKind,
+    text: Box<str>,
+    /// Trivia since the end of the previous token's line.
+    leading: Box<[Trivia]>,
+    /// Trivia up to and including the end of the token's line.
+    trailing: Box<[Trivia]>,
+}
+
+impl GreenToken {
+    pub fn new(kind: TokenKind, text: &str, leading: Vec<Trivia>, trailing: Vec<Trivia>) -> Self {
+        Self {
+            kind,
+            text: Box::from(text),
+            leading: leading.into(),
+            trailing: trailing.into(),
+        }
+    }
+
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    /// The token's text, without its trivia.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn leading(&self) -> &[Trivia] {
+        &self.leading
+    }
+
+    pub fn trailing(&self) -> &[Trivia] {
+        &self.trailing
+    }
+
+    pub fn leading_len(&self) -> u32 {
+        trivia_len(&self.leading)
+    }
+
+    pub fn trailing_len(&self) -> u32 {
+        trivia_len(&self.trailing)
+    }
+
+    /// Length in bytes, including trivia.
+    pub fn full_len(&self) -> u32 {
+        self.leading_len() + self.text.len() as u32 + self.trailing_len()
+    }
+}
+
+fn trivia_len(trivia: &[Trivia]) -> u32 {
+    trivia.iter().map(|trivia| trivia.text.len() as u32).sum()
+}
+
+/// Writes the token's full text, with its trivia.
+impl fmt::Display for GreenToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for trivia in &self.leading {
+            f.write_str(&trivia.text)?;
+        }
+        f.write_str(&self.text)?;
+        for trivia in &self.trailing {
+            f.write_str(&trivia.text)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    /// Length in bytes, including trivia.
+    pub fn full_len(&self) -> u32 {
+        match self {
+            GreenElement::Node(node) => node.full_len(),
+            GreenElement::Token(token) => token.full_len(),
+        }
+    }
+
+    /// How many nodes deep the element goes, counting itself: none for a
+    /// token.
+    pub fn height(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.height(),
+            GreenElement::Token(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for GreenElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GreenElement::Node(node) => node.fmt(f),
+            GreenElement::Token(token) => token.fmt(f),
+        }
+    }
+}
+
+/// An immutable node of the syntax tree. Like [`GreenToken`] it has no
+/// position or parent, so unchanged subtrees can be reused.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    full_len: u32,
+    height: usize,
+    children: Box<[GreenElement]>,
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        let full_len = children.iter().map(GreenElement::full_len).sum();
+        let height = 1 + children.iter().map(GreenElement::height).max().unwrap_or(0);
+        Self {
+            kind,
+            full_len,
+            height,
+            children: children.into(),
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// Length in bytes, including trivia.
+    pub fn full_len(&self) -> u32 {
+        self.full_len
+    }
+
+    /// How many nodes deep the node goes, counting itself.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns a copy of the node with child `index` replaced by `child`. The
+    /// other children are shared.
+    pub fn replace_child(&self, index: usize, child: GreenElement) -> GreenNode {
+        let mut children = self.children.to_vec();
+        children[index] = child;
+        GreenNode::new(self.kind, children)
+    }
+}
+
+/// Writes the node's full text, so a whole tree writes its source back.
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.children.iter().try_for_each(|child| child.fmt(f))
+    }
+}
+
+/// A point in a [`GreenBuilder`] to wrap later children in a node from.
+#[derive(Debug, Clone, Copy)]
+pub struct Checkpoint(usize);
+
+/// Builds a [`GreenNode`] top-down, as a parser walks the input.
+#[derive(Debug, Default)]
+pub struct GreenBuilder {
+    /// Nodes started and not finished, with the index of their first child.
+    parents: Vec<(SyntaxKind, usize)>,
+    children: Vec<GreenElement>,
+}
+
+impl GreenBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a node of `kind`. Children added until it's finished are its own.
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, self.children.len()));
+    }
+
+    /// Start a node of `kind` holding everything added since `checkpoint`,
+    /// for when a node's kind is only known after its first child, like the
+    /// left operand of a binary expression.
+    pub fn start_node_at(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        let Checkpoint(first) = checkpoint;
+        assert!(first <= self.children.len(), "checkpoint is stale");
+        if let Some(&(_, parent_first)) = self.parents.last() {
+            assert!(first >= parent_first, "checkpoint is outside the node");
+        }
+        self.parents.push((kind, first));
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.children.len())
+    }
+
+    /// How deep in the tree a node started at `checkpoint` would go, counting
+    /// the nodes it's in, itself, and the deepest of the children it wraps.
+    pub fn depth_at(&self, checkpoint: Checkpoint) -> usize {
+        let Checkpoint(first) = checkpoint;
+        let wrapped = self.children[first..].iter().map(GreenElement::height);
+        self.parents.len() + 1 + wrapped.max().unwrap_or(0)
+    }
+
+    pub fn token(&mut self, token: GreenToken) {
+        self.children.push(GreenElement::Token(Rc::new(token)));
+    }
+
+    /// Finish the most recently started node.
+    pub fn finish_node(&mut self) {
+        let (kind, first) = self.parents.pop().expect("no node to finish");
+        let children = self.children.split_off(first);
+        self.children
+            .push(GreenElement::Node(Rc::new(GreenNode::new(kind, children))));
+    }
+
+    /// Returns the root, which must be the only node left.
+    pub fn finish(mut self) -> Rc<GreenNode> {
+        assert!(self.parents.is_empty(), "unfinished nodes");
+        match (self.children.pop(), self.children.is_empty()) {
+            (Some(GreenElement::Node(root)), true) => root,
+            _ => panic!("the root must be a single node"),
+        }
+    }
+}
@@ -0,0 +1,833 @@
+use std::mem;
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::scanner::Scanner;
+use crate::source::{FileId, Span};
+use crate::stack;
+use crate::syntax::SyntaxKind;
+use crate::syntax::green::{Checkpoint, GreenBuilder, GreenElement, GreenNode, GreenToken, Trivia};
+use crate::token::{Keyword, TokenKind, decode_string};
+
+/// The maximum number of arguments to a call, and parameters of a function.
+const MAX_ARGUMENTS: usize = 255;
+
+/// How many nodes deep the tree can go, so walking it, or the tree lowered
+/// from it, can't take unbounded stack. Code nested deeper, or a chain of
+/// operators longer, is reported as a stack overflow.
+const MAX_DEPTH: usize = 1024;
+
+/// Tokens an expression never swallows when it's missing, since they end or
+/// start a statement.
+const RECOVERY: &[TokenKind] = &[
+    TokenKind::Semicolon,
+    TokenKind::LeftBrace,
+    TokenKind::RightBrace,
+    TokenKind::RightParen,
+    TokenKind::Keyword(Keyword::Class),
+    TokenKind::Keyword(Keyword::Else),
+    TokenKind::Keyword(Keyword::Fun),
+    TokenKind::Keyword(Keyword::For),
+    TokenKind::Keyword(Keyword::If),
+    TokenKind::Keyword(Keyword::Print),
+    TokenKind::Keyword(Keyword::Return),
+    TokenKind::Keyword(Keyword::Var),
+    TokenKind::Keyword(Keyword::While),
+    TokenKind::Eof,
+];
+
+/// A token with its trivia, ready to add to a tree.
+struct Lexed {
+    token: GreenToken,
+    /// The span of the token's text, for errors.
+    span: Span,
+}
+
+/// Scan `source` and attach its trivia to the tokens around it. A token's
+/// trailing trivia runs to the end of its line, and the rest is leading trivia
+/// of the next token. The last token is an [`TokenKind::Eof`] holding the
+/// trivia after every other token.
+fn lex(source: &str, file: FileId) -> Vec<Lexed> {
+    let tokens = Scanner::with_file(source, file).lossless().tokens();
+    let mut tokens = tokens.iter().peekable();
+    let mut lexed = Vec::new();
+    let mut leading = Vec::new();
+    let mut eof = Span {
+        file,
+        start: 0,
+        end: 0,
+    };
+    loop {
+        while let Some(trivia) = tokens.next_if(|tok| tok.is_trivia()) {
+            leading.push(Trivia::new(trivia.kind(), trivia.lexeme(source)));
+        }
+        let Some(token) = tokens.next() else {
+            break;
+        };
+        let token_leading = mem::take(&mut leading);
+        let mut trailing = Vec::new();
+        while let Some(trivia) = tokens.next_if(|tok| tok.is_trivia()) {
+            let text = trivia.lexeme(source);
+            if trivia.kind() == TokenKind::Whitespace
+                && let Some(newline) = text.find('\n')
+            {
+                // Split the whitespace after the end of the line.
+                let (end_of_line, rest) = text.split_at(newline + 1);
+                trailing.push(Trivia::new(TokenKind::Whitespace, end_of_line));
+                if !rest.is_empty() {
+                    leading.push(Trivia::new(TokenKind::Whitespace, rest));
+                }
+                break;
+            }
+            trailing.push(Trivia::new(trivia.kind(), text));
+        }
+        eof = Span {
+            start: token.span().end,
+            ..token.span().clone()
+        };
+        lexed.push(Lexed {
+            token: GreenToken::new(token.kind(), token.lexeme(source), token_leading, trailing),
+            span: token.span().clone(),
+        });
+    }
+    lexed.push(Lexed {
+        token: GreenToken::new(TokenKind::Eof, "", leading, Vec::new()),
+        span: eof,
+    });
+    lexed
+}
+
+/// Parse `source` into a green tree, and the errors found.
+pub(super) fn parse(source: &str, file: FileId) -> (Rc<GreenNode>, Vec<Error>) {
+    let tokens = lex(source, file);
+    let mut parser = Parser::new(tokens);
+    parser.program();
+    (parser.builder.finish(), parser.errors)
+}
+
+/// Parse `source` as a single expression, under a program node. Tokens after
+/// the expression are unexpected, and go in an error node.
+pub(super) fn parse_expression(source: &str, file: FileId) -> (Rc<GreenNode>, Vec<Error>) {
+    let mut parser = Parser::new(lex(source, file));
+    parser.builder.start_node(SyntaxKind::Program);
+    parser.expression();
+    if !parser.at(TokenKind::Eof) {
+        let span = parser.peek_span();
+        parser.error(Error::UnexpectedToken { span });
+        parser.builder.start_node(SyntaxKind::Error);
+        while !parser.at(TokenKind::Eof) {
+            parser.bump();
+        }
+        parser.builder.finish_node();
+    }
+    parser.bump();
+    parser.builder.finish_node();
+    (parser.builder.finish(), parser.errors)
+}
+
+/// Parse `source` as a block on its own, for reparsing one that was edited,
+/// `depth` nodes deep in its tree. Returns `None` unless the source is
+/// exactly one block, ending with its `}` and any trivia on the line after it.
+pub(super) fn parse_block(
+    source: &str,
+    file: FileId,
+    depth: usize,
+) -> Option<(Rc<GreenNode>, Vec<Error>)> {
+    let tokens = lex(source, file);
+    let eof = tokens.last().expect("lexing ends with Eof");
+    if tokens[0].token.kind() != TokenKind::LeftBrace || !eof.token.leading().is_empty() {
+        return None;
+    }
+    let mut parser = Parser::new(tokens);
+    parser.outer_depth = depth;
+    parser.block();
+    let consumed = parser.at(TokenKind::Eof);
+    let block = parser.builder.finish();
+    let closed = matches!(
+        block.children().last(),
+        Some(GreenElement::Token(token)) if token.kind() == TokenKind::RightBrace
+    );
+    (consumed && closed).then_some((block, parser.errors))
+}
+
+/// What kind of function the `function` rule is parsing.
+#[derive(Debug, Clone, Copy)]
+enum FunctionKind {
+    Function,
+    Method,
+}
+
+/// Recursive descent parser for the grammar of Lox, building a lossless tree,
+/// which [`crate::parser::Parser`] lowers for the interpreter. It never stops
+/// at an error: it records it, and either leaves the missing part out or
+/// wraps unexpected tokens in [`SyntaxKind::Error`] nodes. Expression rules
+/// return the kind of the node they built.
+///
+/// After an error, later ones are dropped until the end of the declaration
+/// it's in, whose remaining tokens are skipped: see `synchronize`.
+struct Parser {
+    /// The tokens left, in reverse, so the next is last.
+    tokens: Vec<Lexed>,
+    /// The kind of the last token consumed.
+    previous: Option<TokenKind>,
+    builder: GreenBuilder,
+    errors: Vec<Error>,
+    /// Whether an error was found since parsing last resumed, so any more
+    /// are likely fallout from it.
+    recovering: bool,
+    /// How many nodes deep the tree being built is in a bigger one.
+    outer_depth: usize,
+}
+
+impl Parser {
+    fn new(mut tokens: Vec<Lexed>) -> Self {
+        tokens.reverse();
+        Self {
+            tokens,
+            previous: None,
+            builder: GreenBuilder::new(),
+            errors: Vec::new(),
+            recovering: false,
+            outer_depth: 0,
+        }
+    }
+
+    fn peek(&self) -> TokenKind {
+        self.tokens
+            .last()
+            .map_or(TokenKind::Eof, |lexed| lexed.token.kind())
+    }
+
+    fn at(&self, kind: TokenKind) -> bool {
+        self.peek() == kind
+    }
+
+    /// The span of the next token.
+    fn peek_span(&self) -> Span {
+        self.tokens
+            .last()
+            .expect("Eof is never consumed before the end")
+            .span
+            .clone()
+    }
+
+    /// Add the next token to the current node.
+    fn bump(&mut self) {
+        let lexed = self.tokens.pop().expect("no token to consume");
+        self.previous = Some(lexed.token.kind());
+        self.builder.token(lexed.token);
+    }
+
+    /// Add the next token, a string literal or a segment of one, to the
+    /// current node, and record the first invalid escape in it, if any.
+    fn bump_string(&mut self) {
+        let lexed = self.tokens.last().expect("no token to consume");
+        if let Err(error) = decode_string(lexed.token.kind(), lexed.token.text(), &lexed.span) {
+            // Not a syntax error, so there's nothing to recover from.
+            self.errors.push(error);
+        }
+        self.bump();
+    }
+
+    /// Consume the next token if it is of `kind`, otherwise error with what
+    /// was `expected`.
+    fn expect(&mut self, kind: TokenKind, expected: &'static str) {
+        if self.at(kind) {
+            self.bump();
+        } else {
+            let span = self.peek_span();
+            self.error(Error::Expected { expected, span });
+        }
+    }
+
+    /// Record `error`, unless the parser is recovering from an earlier one: a
+    /// mistake often fails several rules in turn, like an expression missing
+    /// before a `;` and then the `;` missing after it.
+    fn error(&mut self, error: Error) {
+        if !self.recovering {
+            self.recovering = true;
+            self.errors.push(error);
+        }
+    }
+
+    /// Skip what's left of a declaration after an error in it, so parsing
+    /// resumes at the next one: past a `;`, or in front of a keyword starting
+    /// a declaration, the `}` closing the block, or the end. The skipped
+    /// tokens go in an error node.
+    fn synchronize(&mut self) {
+        self.recovering = false;
+        let resume = |kind: TokenKind| {
+            kind.starts_declaration() || matches!(kind, TokenKind::RightBrace | TokenKind::Eof)
+        };
+        if self.previous == Some(TokenKind::Semicolon) || resume(self.peek()) {
+            return;
+        }
+        self.builder.start_node(SyntaxKind::Error);
+        while !resume(self.peek()) {
+            let semicolon = self.at(TokenKind::Semicolon);
+            self.bump();
+            if semicolon {
+                break;
+            }
+        }
+        self.builder.finish_node();
+    }
+
+    /// Whether a node started at `checkpoint` would be deeper than
+    /// [`MAX_DEPTH`], recording a stack overflow at the next token if so.
+    fn too_deep(&mut self, checkpoint: Checkpoint) -> bool {
+        if self.outer_depth + self.builder.depth_at(checkpoint) <= MAX_DEPTH {
+            return false;
+        }
+        let span = self.peek_span();
+        self.error(Error::StackOverflow { span });
+        true
+    }
+
+    /// Skip code nested too deeply to parse, up to the `;` or the closing
+    /// bracket ending what it's in, into an error node. Brackets in it are
+    /// skipped in pairs, and a block ends it.
+    fn skip_nested(&mut self) {
+        self.builder.start_node(SyntaxKind::Error);
+        let mut open = 0usize;
+        loop {
+            match self.peek() {
+                TokenKind::Eof => break,
+                TokenKind::Semicolon | TokenKind::RightParen | TokenKind::RightBrace
+                    if open == 0 =>
+                {
+                    break;
+                }
+                TokenKind::LeftParen | TokenKind::LeftBrace => open += 1,
+                TokenKind::RightParen => open -= 1,
+                TokenKind::RightBrace => {
+                    open -= 1;
+                    if open == 0 {
+                        self.bump();
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            self.bump();
+        }
+        self.builder.finish_node();
+    }
+
+    /// Wrap the next token in an error node, so a loop over it makes progress.
+    fn bump_error(&mut self) {
+        self.builder.start_node(SyntaxKind::Error);
+        self.bump();
+        self.builder.finish_node();
+    }
+
+    /// Parse a declaration, or wrap the next token in an error node if it
+    /// can't start one, so every call makes progress. Then skip the rest of
+    /// the declaration if it had an error.
+    fn progressing_declaration(&mut self) {
+        // Tokens that only close or continue something are stray here.
+        if matches!(
+            self.peek(),
+            TokenKind::RightBrace | TokenKind::RightParen | TokenKind::Keyword(Keyword::Else)
+        ) {
+            let span = self.peek_span();
+            self.error(Error::ParseError { span });
+            self.bump_error();
+        } else {
+            let remaining = self.tokens.len();
+            self.declaration();
+            if self.tokens.len() == remaining {
+                self.bump_error();
+            }
+        }
+        if self.recovering {
+            self.synchronize();
+        }
+    }
+
+    /// program -> declaration* EOF ;
+    fn program(&mut self) {
+        self.builder.start_node(SyntaxKind::Program);
+        while !self.at(TokenKind::Eof) {
+            self.progressing_declaration();
+        }
+        self.bump();
+        self.builder.finish_node();
+    }
+
+    /// declaration -> classDecl
+    ///              | funDecl
+    ///              | varDecl
+    ///              | statement ;
+    fn declaration(&mut self) {
+        if self.too_deep(self.builder.checkpoint()) {
+            return self.skip_nested();
+        }
+        stack::grow(|| match self.peek() {
+            TokenKind::Keyword(Keyword::Class) => self.class_declaration(),
+            TokenKind::Keyword(Keyword::Fun) => {
+                self.builder.start_node(SyntaxKind::FunDecl);
+                self.bump(); // consume fun
+                self.function(FunctionKind::Function);
+                self.builder.finish_node();
+            }
+            TokenKind::Keyword(Keyword::Var) => self.var_declaration(),
+            _ => self.statement(),
+        })
+    }
+
+    /// classDecl -> "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}" ;
+    fn class_declaration(&mut self) {
+        self.builder.start_node(SyntaxKind::ClassDecl);
+        self.bump(); // consume class
+        self.expect(TokenKind::Identifier, "class name");
+        if self.at(TokenKind::Less) {
+            self.bump();
+            self.expect(TokenKind::Identifier, "superclass name");
+        }
+        if !self.at(TokenKind::LeftBrace) {
+            let span = self.peek_span();
+            self.error(Error::Expected {
+                expected: "'{' before class body",
+                span,
+            });
+            self.builder.finish_node();
+            return;
+        }
+        self.bump();
+        while !self.at(TokenKind::RightBrace) && !self.at(TokenKind::Eof) {
+            if self.at(TokenKind::Identifier) {
+                self.function(FunctionKind::Method);
+            } else {
+                let span = self.peek_span();
+                self.error(Error::Expected {
+                    expected: "method name",
+                    span,
+                });
+                self.bump_error();
+            }
+        }
+        self.expect(TokenKind::RightBrace, "'}' after class body");
+        self.builder.finish_node();
+    }
+
+    /// function -> IDENTIFIER "(" parameters? ")" block ;
+    /// parameters -> IDENTIFIER ( "," IDENTIFIER )* ;
+    fn function(&mut self, kind: FunctionKind) {
+        let (expected_name, expected_paren) = match kind {
+            FunctionKind::Function => ("function name", "'(' after function name"),
+            FunctionKind::Method => ("method name", "'(' after method name"),
+        };
+        self.builder.start_node(SyntaxKind::Function);
+        self.expect(TokenKind::Identifier, expected_name);
+
+        self.builder.start_node(SyntaxKind::ParamList);
+        self.expect(TokenKind::LeftParen, expected_paren);
+        if !self.at(TokenKind::RightParen) {
+            let mut count = 0;
+            loop {
+                if count == MAX_ARGUMENTS {
+                    // Not a syntax error, so there's nothing to recover from.
+                    let span = self.peek_span();
+                    self.errors.push(Error::TooManyParameters {
+                        max: MAX_ARGUMENTS,
+                        span,
+                    });
+                }
+                self.expect(TokenKind::Identifier, "parameter name");
+                count += 1;
+                if self.at(TokenKind::Comma) {
+                    self.bump();
+                } else {
+                    break;
+                }
+            }
+        }
+        self.expect(TokenKind::RightParen, "')' after parameters");
+        self.builder.finish_node();
+
+        if self.at(TokenKind::LeftBrace) {
+            self.block();
+        } else {
+            let span = self.peek_span();
+            self.error(Error::Expected {
+                expected: "'{' before function body",
+                span,
+            });
+        }
+        self.builder.finish_node();
+    }
+
+    /// varDecl -> "var" IDENTIFIER ( "=" expression )? ";" ;
+    fn var_declaration(&mut self) {
+        self.builder.start_node(SyntaxKind::VarDecl);
+        self.bump(); // consume var
+        self.expect(TokenKind::Identifier, "variable name");
+        if self.at(TokenKind::Equal) {
+            self.bump();
+            self.expression();
+        }
+        self.expect(TokenKind::Semicolon, "';' after variable declaration");
+        self.builder.finish_node();
+    }
+
+    /// statement -> exprStmt
+    ///            | forStmt
+    ///            | ifStmt
+    ///            | printStmt
+    ///            | returnStmt
+    ///            | whileStmt
+    ///            | block ;
+    fn statement(&mut self) {
+        if self.too_deep(self.builder.checkpoint()) {
+            return self.skip_nested();
+        }
+        stack::grow(|| match self.peek() {
+            TokenKind::Keyword(Keyword::For) => self.for_statement(),
+            TokenKind::Keyword(Keyword::If) => self.if_statement(),
+            TokenKind::Keyword(Keyword::Print) => {
+                self.builder.start_node(SyntaxKind::PrintStmt);
+                self.bump(); // consume print
+                self.expression();
+                self.expect(TokenKind::Semicolon, "';' after value");
+                self.builder.finish_node();
+            }
+            TokenKind::Keyword(Keyword::Return) => {
+                self.builder.start_node(SyntaxKind::ReturnStmt);
+                self.bump(); // consume return
+                if !self.at(TokenKind::Semicolon) {
+                    self.expression();
+                }
+                self.expect(TokenKind::Semicolon, "';' after return value");
+                self.builder.finish_node();
+            }
+            TokenKind::Keyword(Keyword::While) => {
+                self.builder.start_node(SyntaxKind::WhileStmt);
+                self.bump(); // consume while
+                self.expect(TokenKind::LeftParen, "'(' after 'while'");
+                self.expression();
+                self.expect(TokenKind::RightParen, "')' after condition");
+                self.statement();
+                self.builder.finish_node();
+            }
+            TokenKind::LeftBrace => self.block(),
+            _ => self.expression_statement(),
+        })
+    }
+
+    /// block -> "{" declaration* "}" ;
+    fn block(&mut self) {
+        self.builder.start_node(SyntaxKind::Block);
+        self.bump(); // consume left brace
+        while !self.at(TokenKind::RightBrace) && !self.at(TokenKind::Eof) {
+            self.progressing_declaration();
+        }
+        self.expect(TokenKind::RightBrace, "'}' after block");
+        self.builder.finish_node();
+    }
+
+    /// forStmt -> "for" "(" ( varDecl | exprStmt | ";" )
+    ///            expression? ";"
+    ///            expression? ")" statement ;
+    fn for_statement(&mut self) {
+        self.builder.start_node(SyntaxKind::ForStmt);
+        self.bump(); // consume for
+        self.expect(TokenKind::LeftParen, "'(' after 'for'");
+        match self.peek() {
+            TokenKind::Semicolon => self.bump(),
+            TokenKind::Keyword(Keyword::Var) => self.var_declaration(),
+            _ => self.expression_statement(),
+        }
+        if !self.at(TokenKind::Semicolon) {
+            self.expression();
+        }
+        self.expect(TokenKind::Semicolon, "';' after loop condition");
+        if !self.at(TokenKind::RightParen) {
+            self.expression();
+        }
+        self.expect(TokenKind::RightParen, "')' after for clauses");
+        self.statement();
+        self.builder.finish_node();
+    }
+
+    /// ifStmt -> "if" "(" expression ")" statement ( "else" statement )? ;
+    fn if_statement(&mut self) {
+        self.builder.start_node(SyntaxKind::IfStmt);
+        self.bump(); // consume if
+        self.expect(TokenKind::LeftParen, "'(' after 'if'");
+        self.expression();
+        self.expect(TokenKind::RightParen, "')' after if condition");
+        self.statement();
+        if self.at(TokenKind::Keyword(Keyword::Else)) {
+            self.bump();
+            self.statement();
+        }
+        self.builder.finish_node();
+    }
+
+    /// exprStmt -> expression ";" ;
+    fn expression_statement(&mut self) {
+        self.builder.start_node(SyntaxKind::ExprStmt);
+        self.expression();
+        self.expect(TokenKind::Semicolon, "';' after expression");
+        self.builder.finish_node();
+    }
+
+    /// expression -> assignment ;
+    fn expression(&mut self) -> SyntaxKind {
+        self.assignment()
+    }
+
+    /// assignment -> ( call "." )? IDENTIFIER "=" assignment
+    ///             | logic_or ;
+    fn assignment(&mut self) -> SyntaxKind {
+        let checkpoint = self.builder.checkpoint();
+        let target = self.or();
+        if !self.at(TokenKind::Equal) {
+            return target;
+        }
+        self.builder.start_node_at(checkpoint, SyntaxKind::Assign);
+        if !matches!(target, SyntaxKind::Variable | SyntaxKind::Get) {
+            // Not a syntax error, so there's nothing to recover from.
+            let span = self.peek_span();
+            self.errors.push(Error::InvalidAssignmentTarget { span });
+        }
+        self.bump(); // consume equal
+        self.assignment();
+        self.builder.finish_node();
+        SyntaxKind::Assign
+    }
+
+    /// Parse a left-associative chain of `operand`s separated by any of
+    /// `operators`, as nodes of `kind`.
+    fn binary(
+        &mut self,
+        kind: SyntaxKind,
+        operators: &[TokenKind],
+        operand: fn(&mut Self) -> SyntaxKind,
+    ) -> SyntaxKind {
+        let checkpoint = self.builder.checkpoint();
+        let mut completed = operand(self);
+        while operators.contains(&self.peek()) {
+            if self.too_deep(checkpoint) {
+                self.skip_nested();
+                break;
+            }
+            self.builder.start_node_at(checkpoint, kind);
+            self.bump(); // consume operator
+            operand(self);
+            self.builder.finish_node();
+            completed = kind;
+        }
+        completed
+    }
+
+    /// logic_or -> logic_and ( "or" logic_and )* ;
+    fn or(&mut self) -> SyntaxKind {
+        let operators = [TokenKind::Keyword(Keyword::Or)];
+        self.binary(SyntaxKind::Logical, &operators, Self::and)
+    }
+
+    /// logic_and -> equality ( "and" equality )* ;
+    fn and(&mut self) -> SyntaxKind {
+        let operators = [TokenKind::Keyword(Keyword::And)];
+        self.binary(SyntaxKind::Logical, &operators, Self::equality)
+    }
+
+    /// equality -> comparison ( ( "!=" | "==" ) comparison )* ;
+    fn equality(&mut self) -> SyntaxKind {
+        let operators = [TokenKind::BangEqual, TokenKind::EqualEqual];
+        self.binary(SyntaxKind::Binary, &operators, Self::comparison)
+    }
+
+    /// comparison -> term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+    fn comparison(&mut self) -> SyntaxKind {
+        let operators = [
+            TokenKind::Greater,
+            TokenKind::GreaterEqual,
+            TokenKind::Less,
+            TokenKind::LessEqual,
+        ];
+        self.binary(SyntaxKind::Binary, &operators, Self::term)
+    }
+
+    /// term -> factor ( ( "-" | "+" ) factor )* ;
+    fn term(&mut self) -> SyntaxKind {
+        let operators = [TokenKind::Minus, TokenKind::Plus];
+        self.binary(SyntaxKind::Binary, &operators, Self::factor)
+    }
+
+    /// factor -> unary ( ( "/" | "*" ) unary )* ;
+    fn factor(&mut self) -> SyntaxKind {
+        let operators = [TokenKind::Slash, TokenKind::Star];
+        self.binary(SyntaxKind::Binary, &operators, Self::unary)
+    }
+
+    /// unary -> ( "!" | "-" ) unary
+    ///        | call ;
+    fn unary(&mut self) -> SyntaxKind {
+        stack::grow(|| {
+            // Every nested expression is parsed through here.
+            if self.too_deep(self.builder.checkpoint()) {
+                self.skip_nested();
+                return SyntaxKind::Error;
+            }
+            if !matches!(self.peek(), TokenKind::Bang | TokenKind::Minus) {
+                return self.call();
+            }
+            self.builder.start_node(SyntaxKind::Unary);
+            self.bump(); // consume operator
+            self.unary();
+            self.builder.finish_node();
+            SyntaxKind::Unary
+        })
+    }
+
+    /// call -> primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+    fn call(&mut self) -> SyntaxKind {
+        let checkpoint = self.builder.checkpoint();
+        let mut completed = self.primary();
+        loop {
+            match self.peek() {
+                TokenKind::LeftParen | TokenKind::Dot if self.too_deep(checkpoint) => {
+                    self.skip_nested();
+                    return completed;
+                }
+                TokenKind::LeftParen => {
+                    self.builder.start_node_at(checkpoint, SyntaxKind::Call);
+                    self.arguments();
+                    self.builder.finish_node();
+                    completed = SyntaxKind::Call;
+                }
+                TokenKind::Dot => {
+                    self.builder.start_node_at(checkpoint, SyntaxKind::Get);
+                    self.bump(); // consume dot
+                    self.expect(TokenKind::Identifier, "property name after '.'");
+                    self.builder.finish_node();
+                    completed = SyntaxKind::Get;
+                }
+                _ => return completed,
+            }
+        }
+    }
+
+    /// arguments -> expression ( "," expression )* ;
+    fn arguments(&mut self) {
+        self.builder.start_node(SyntaxKind::ArgList);
+        self.bump(); // consume left parenthesis
+        if !self.at(TokenKind::RightParen) {
+            let mut count = 0;
+            loop {
+                if count == MAX_ARGUMENTS {
+                    // Not a syntax error, so there's nothing to recover from.
+                    let span = self.peek_span();
+                    self.errors.push(Error::TooManyArguments {
+                        max: MAX_ARGUMENTS,
+                        span,
+                    });
+                }
+                self.expression();
+                count += 1;
+                if self.at(TokenKind::Comma) {
+                    self.bump();
+                } else {
+                    break;
+                }
+            }
+        }
+        self.expect(TokenKind::RightParen, "')' after arguments");
+        self.builder.finish_node();
+    }
+
+    /// interpolation -> STRING_HEAD expression
+    ///                  ( STRING_MIDDLE expression )* STRING_TAIL ;
+    fn interpolation(&mut self) -> SyntaxKind {
+        self.builder.start_node(SyntaxKind::Interpolation);
+        loop {
+            // Point any error at the `${` opening the hole.
+            let segment = self.peek_span();
+            self.bump_string(); // consume head or middle
+            self.expression();
+            match self.peek() {
+                TokenKind::StringMiddle => continue,
+                TokenKind::StringTail => self.bump_string(),
+                _ => self.error(Error::UnterminatedInterpolation {
+                    span: Span {
+                        start: segment.end - 2,
+                        ..segment
+                    },
+                }),
+            }
+            break;
+        }
+        self.builder.finish_node();
+        SyntaxKind::Interpolation
+    }
+
+    /// primary -> NUMBER | STRING | "true" | "false" | "nil" | "this"
+    ///          | interpolation
+    ///          | "(" expression ")"
+    ///          | IDENTIFIER
+    ///          | "super" "." IDENTIFIER ;
+    fn primary(&mut self) -> SyntaxKind {
+        let kind = match self.peek() {
+            TokenKind::Keyword(Keyword::True | Keyword::False | Keyword::Nil)
+            | TokenKind::Number
+            | TokenKind::String => SyntaxKind::Literal,
+            TokenKind::Keyword(Keyword::This) => SyntaxKind::This,
+            TokenKind::Identifier => SyntaxKind::Variable,
+            TokenKind::StringHead => return self.interpolation(),
+            TokenKind::Keyword(Keyword::Super) => {
+                self.builder.start_node(SyntaxKind::Super);
+                self.bump(); // consume super
+                self.expect(TokenKind::Dot, "'.' after 'super'");
+                self.expect(TokenKind::Identifier, "superclass method name");
+                self.builder.finish_node();
+                return SyntaxKind::Super;
+            }
+            TokenKind::LeftParen => {
+                self.builder.start_node(SyntaxKind::Grouping);
+                let paren = self.peek_span();
+                self.bump(); // consume left parenthesis
+                self.expression();
+                if self.at(TokenKind::RightParen) {
+                    self.bump();
+                } else {
+                    let close = self.peek_span();
+                    self.error(Error::UnclosedParenthesis { span: paren, close });
+                }
+                self.builder.finish_node();
+                return SyntaxKind::Grouping;
+            }
+            _ => return self.missing_expression(),
+        };
+        self.builder.start_node(kind);
+        if self.at(TokenKind::String) {
+            self.bump_string();
+        } else {
+            self.bump();
+        }
+        self.builder.finish_node();
+        kind
+    }
+
+    /// Record that an expression was expected, and wrap the next token in an
+    /// error node unless it can end or start a statement.
+    fn missing_expression(&mut self) -> SyntaxKind {
+        let span = self.peek_span();
+        let error = match self.peek() {
+            TokenKind::UnterminatedString => Error::UnterminatedString { span },
+            TokenKind::UnterminatedBlockComment => Error::UnterminatedBlockComment { span },
+            TokenKind::InvalidNumber => Error::InvalidNumber { span },
+            TokenKind::InvalidCharacter => Error::InvalidCharacter { span },
+            _ => Error::ParseError { span },
+        };
+        self.error(error);
+        self.builder.start_node(SyntaxKind::Error);
+        if !RECOVERY.contains(&self.peek()) {
+            self.bump();
+        }
+        self.builder.finish_node();
+        SyntaxKind::Error
+    }
+}
@@ -0,0 +1,356 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::source::{FileId, Span};
+use crate::syntax::SyntaxKind;
+use crate::syntax::green::{GreenElement, GreenNode, GreenToken, Trivia};
+use crate::token::TokenKind;
+
+/// A node of the syntax tree, positioned in its file. Created on demand over
+/// a [`GreenNode`] while walking down from the root, and cheap to clone.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    /// Byte offset of the node, including its leading trivia.
+    offset: u32,
+    file: FileId,
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>, file: FileId) -> Self {
+        Self(Rc::new(NodeData {
+            green,
+            parent: None,
+            offset: 0,
+            file,
+        }))
+    }
+
+    fn new_child(&self, green: Rc<GreenNode>, offset: u32) -> Self {
+        Self(Rc::new(NodeData {
+            green,
+            parent: Some(self.clone()),
+            offset,
+            file: self.0.file,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind()
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    /// Returns the node, then its parent, and so on up to the root.
+    pub fn ancestors(&self) -> impl Iterator<Item = SyntaxNode> + use<> {
+        std::iter::successors(Some(self.clone()), SyntaxNode::parent)
+    }
+
+    /// The node's span, including the trivia around it.
+    pub fn full_span(&self) -> Span {
+        let start = self.0.offset;
+        Span {
+            file: self.0.file,
+            start,
+            end: start + self.0.green.full_len(),
+        }
+    }
+
+    /// The node's span, without the trivia around it.
+    pub fn span(&self) -> Span {
+        let full = self.full_span();
+        match (self.first_token(), self.last_token()) {
+            (Some(first), Some(last)) => Span {
+                start: first.span().start,
+                end: last.span().end,
+                ..full
+            },
+            // No tokens, so no trivia either.
+            _ => full,
+        }
+    }
+
+    /// Returns the child nodes and tokens, in order.
+    pub fn children_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> + use<> {
+        let parent = self.clone();
+        let mut offset = self.0.offset;
+        let mut index = 0;
+        std::iter::from_fn(move || {
+            let child = parent.0.green.children().get(index)?;
+            index += 1;
+            let start = offset;
+            offset += child.full_len();
+            Some(match child {
+                GreenElement::Node(green) => {
+                    SyntaxElement::Node(parent.new_child(Rc::clone(green), start))
+                }
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green: Rc::clone(green),
+                    parent: parent.clone(),
+                    offset: start,
+                }),
+            })
+        })
+    }
+
+    /// Returns the child nodes, in order.
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> + use<> {
+        self.children_with_tokens()
+            .filter_map(SyntaxElement::into_node)
+    }
+
+    /// Returns the node and everything below it, in source order, with each
+    /// node before its children.
+    pub fn descendants_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> + use<> {
+        let mut stack = vec![SyntaxElement::Node(self.clone())];
+        std::iter::from_fn(move || {
+            let element = stack.pop()?;
+            if let SyntaxElement::Node(node) = &element {
+                let children: Vec<_> = node.children_with_tokens().collect();
+                stack.extend(children.into_iter().rev());
+            }
+            Some(element)
+        })
+    }
+
+    /// Returns the node and every node below it, with each node before its
+    /// children.
+    pub fn descendants(&self) -> impl Iterator<Item = SyntaxNode> + use<> {
+        self.descendants_with_tokens()
+            .filter_map(SyntaxElement::into_node)
+    }
+
+    /// Returns every token below the node, in source order.
+    pub fn tokens(&self) -> impl Iterator<Item = SyntaxToken> + use<> {
+        self.descendants_with_tokens()
+            .filter_map(SyntaxElement::into_token)
+    }
+
+    pub fn first_token(&self) -> Option<SyntaxToken> {
+        self.tokens().next()
+    }
+
+    pub fn last_token(&self) -> Option<SyntaxToken> {
+        self.tokens().last()
+    }
+
+    /// Returns the deepest node or token whose full span covers `start..end`,
+    /// the first if several do.
+    pub fn covering_element(&self, start: u32, end: u32) -> SyntaxElement {
+        let mut node = self.clone();
+        loop {
+            let child = node.children_with_tokens().find(|child| {
+                let span = child.full_span();
+                span.start <= start && end <= span.end
+            });
+            match child {
+                Some(SyntaxElement::Node(child)) => node = child,
+                Some(token) => return token,
+                None => return SyntaxElement::Node(node),
+            }
+        }
+    }
+
+    /// Returns the root of a new tree, with this node replaced by `green`.
+    /// Everything but the node and its ancestors is shared with this tree.
+    pub fn replace_with(&self, green: Rc<GreenNode>) -> Rc<GreenNode> {
+        match self.parent() {
+            None => green,
+            Some(parent) => parent.replace_child(
+                &SyntaxElement::Node(self.clone()),
+                GreenElement::Node(green),
+            ),
+        }
+    }
+
+    fn replace_child(&self, child: &SyntaxElement, green: GreenElement) -> Rc<GreenNode> {
+        let index = self
+            .children_with_tokens()
+            .position(|element| element == *child)
+            .expect("a child of its parent");
+        self.replace_with(Rc::new(self.0.green.replace_child(index, green)))
+    }
+}
+
+/// Nodes are equal if they're the same node of the same tree.
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+/// Writes the node's full text, with its trivia.
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.green.fmt(f)
+    }
+}
+
+/// Writes the tree below the node, one node or token per line, like:
+///
+/// ```text
+/// Program@0..10
+///   PrintStmt@0..8
+///     Keyword(Print)@0..5 "print"
+///     Literal@6..7
+///       Number@6..7 "1"
+///     Semicolon@7..8 ";"
+///   Eof@10..10 ""
+/// ```
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let root_depth = self.ancestors().count();
+        let depth = |node: &SyntaxNode| node.ancestors().count() - root_depth;
+        for element in self.descendants_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) => {
+                    let span = node.span();
+                    let indent = depth(&node) * 2;
+                    writeln!(
+                        f,
+                        "{:indent$}{:?}@{}..{}",
+                        "",
+                        node.kind(),
+                        span.start,
+                        span.end
+                    )?;
+                }
+                SyntaxElement::Token(token) => {
+                    let indent = (depth(&token.parent) + 1) * 2;
+                    writeln!(f, "{:indent$}{token:?}", "")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A token of the syntax tree, positioned in its file.
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    parent: SyntaxNode,
+    /// Byte offset of the token, including its leading trivia.
+    offset: u32,
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> TokenKind {
+        self.green.kind()
+    }
+
+    pub fn green(&self) -> &Rc<GreenToken> {
+        &self.green
+    }
+
+    /// The token's text, without its trivia.
+    pub fn text(&self) -> &str {
+        self.green.text()
+    }
+
+    pub fn leading(&self) -> &[Trivia] {
+        self.green.leading()
+    }
+
+    pub fn trailing(&self) -> &[Trivia] {
+        self.green.trailing()
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+
+    /// Returns the root of a new tree, with this token replaced by `green`.
+    /// Everything but the token and its ancestors is shared with this tree.
+    pub fn replace_with(&self, green: Rc<GreenToken>) -> Rc<GreenNode> {
+        self.parent.replace_child(
+            &SyntaxElement::Token(self.clone()),
+            GreenElement::Token(green),
+        )
+    }
+
+    /// The token's span, including its trivia.
+    pub fn full_span(&self) -> Span {
+        Span {
+            file: self.parent.0.file,
+            start: self.offset,
+            end: self.offset + self.green.full_len(),
+        }
+    }
+
+    /// The token's span, without its trivia.
+    pub fn span(&self) -> Span {
+        let start = self.offset + self.green.leading_len();
+        Span {
+            file: self.parent.0.file,
+            start,
+            end: start + self.green.text().len() as u32,
+        }
+    }
+}
+
+impl PartialEq for SyntaxToken {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.green, &other.green) && self.offset == other.offset
+    }
+}
+
+/// Writes the token's full text, with its trivia.
+impl fmt::Display for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.green.fmt(f)
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let span = self.span();
+        write!(
+            f,
+            "{:?}@{}..{} {:?}",
+            self.kind(),
+            span.start,
+            span.end,
+            self.text()
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxElement {
+    pub fn into_node(self) -> Option<SyntaxNode> {
+        match self {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        }
+    }
+
+    pub fn into_token(self) -> Option<SyntaxToken> {
+        match self {
+            SyntaxElement::Node(_) => None,
+            SyntaxElement::Token(token) => Some(token),
+        }
+    }
+
+    /// The element's span, including its trivia.
+    pub fn full_span(&self) -> Span {
+        match self {
+            SyntaxElement::Node(node) => node.full_span(),
+            SyntaxElement::Token(token) => token.full_span(),
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::scanner::Scanner;
+use crate::source::{Edit, Span};
+use crate::syntax::green::GreenToken;
+use crate::syntax::{Parse, SyntaxElement, SyntaxKind, parse_with_file, parser};
+use crate::token::{TokenKind, decode_string};
+
+/// Reparse `parse` after `edit`, which made `source`. Tries, in turn, to
+/// replace just the token the edit is in, or the innermost block it's in,
+/// then falls back to parsing the whole source.
+pub(super) fn reparse(parse: &Parse, source: &str, edit: &Edit) -> Parse {
+    reparse_token(parse, source, edit)
+        .or_else(|| reparse_block(parse, source, edit))
+        .unwrap_or_else(|| parse_with_file(source, parse.file))
+}
+
+/// Replace the token the edit is inside of, if it scans as a single token of
+/// the same kind afterwards. Only identifiers and plain strings are tried: a
+/// scanner reading other tokens, like numbers, peeks past them.
+fn reparse_token(parse: &Parse, source: &str, edit: &Edit) -> Option<Parse> {
+    let (start, end) = (edit.range.start as u32, edit.range.end as u32);
+    let SyntaxElement::Token(token) = parse.syntax().covering_element(start, end) else {
+        return None;
+    };
+    let span = token.span();
+    if !matches!(token.kind(), TokenKind::Identifier | TokenKind::String)
+        || start < span.start
+        || end > span.end
+    {
+        return None;
+    }
+
+    let new_end = (span.end as isize + edit.delta()) as u32;
+    let text = &source[span.start as usize..new_end as usize];
+    match Scanner::new(text).lossless().tokens()[..] {
+        [ref scanned] if scanned.kind() == token.kind() => {}
+        _ => return None,
+    }
+    let green = GreenToken::new(
+        token.kind(),
+        text,
+        token.leading().to_vec(),
+        token.trailing().to_vec(),
+    );
+    // Errors point at whole tokens, so those past the start of this one are
+    // at its end or after it. The exception is an invalid escape inside a
+    // string, which is checked again.
+    let shift = |offset: u32| {
+        if offset <= span.start {
+            offset
+        } else {
+            (offset as isize + edit.delta()) as u32
+        }
+    };
+    let escape_inside = |error: &&Error| {
+        matches!(error, Error::InvalidEscape { .. })
+            && span.start < error.span().start
+            && error.span().end <= span.end
+    };
+    let mut errors: Vec<_> = parse
+        .errors
+        .iter()
+        .filter(|error| !escape_inside(error))
+        .map(|error| {
+            error.clone().map_spans(|span| Span {
+                file: span.file,
+                start: shift(span.start),
+                end: shift(span.end),
+            })
+        })
+        .collect();
+    if token.kind() == TokenKind::String {
+        let new_span = Span {
+            end: new_end,
+            ..span.clone()
+        };
+        if let Err(error) = decode_string(token.kind(), text, &new_span) {
+            let index = errors
+                .iter()
+                .position(|error| error.span().start > span.start)
+                .unwrap_or(errors.len());
+            errors.insert(index, error);
+        }
+    }
+    Some(Parse {
+        green: token.replace_with(Rc::new(green)),
+        file: parse.file,
+        errors,
+    })
+}
+
+/// Reparse the innermost block the edit is between the braces of, if it's
+/// still one block afterwards. A block whose `}` went missing, or was only
+/// skipped over in recovery, isn't tried: the code after it parsed
+/// differently, and would again.
+fn reparse_block(parse: &Parse, source: &str, edit: &Edit) -> Option<Parse> {
+    let (start, end) = (edit.range.start as u32, edit.range.end as u32);
+    let covering = match parse.syntax().covering_element(start, end) {
+        SyntaxElement::Node(node) => node,
+        SyntaxElement::Token(token) => token.parent(),
+    };
+    let (block, open, close) = covering
+        .ancestors()
+        .filter(|node| node.kind() == SyntaxKind::Block)
+        .find_map(|block| {
+            let open = block.first_token()?.span();
+            let close = block.children_with_tokens().last()?.into_token()?;
+            let inside = close.kind() == TokenKind::RightBrace
+                && open.end <= start
+                && end <= close.span().start;
+            inside.then(|| (block, open, close.span()))
+        })?;
+
+    let full = block.full_span();
+    let text = &source[full.start as usize..edit.map_offset(full.end) as usize];
+    // The nodes the block is in, which were all open when it started.
+    let depth = block.ancestors().count() - 1;
+    let (green, errors) = parser::parse_block(text, parse.file, depth)?;
+
+    // Errors from outside the block stay, or move with the code after it.
+    let outside = |before: bool| {
+        parse
+            .errors
+            .iter()
+            .filter(move |error| {
+                let start = error.span().start;
+                if before {
+                    start <= open.start
+                } else {
+                    start >= close.end
+                }
+            })
+            .map(|error| error.clone().map_spans(|span| edit.map_span(span)))
+    };
+    let inside = errors.into_iter().map(|error| {
+        error.map_spans(|span| Span {
+            file: span.file,
+            start: span.start + full.start,
+            end: span.end + full.start,
+        })
+    });
+    Some(Parse {
+        green: block.replace_with(green),
+        file: parse.file,
+        errors: outside(true).chain(inside).chain(outside(false)).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::syntax::parse;
+
+    const SOURCE: &str = indoc! {r#"
+        var greeting = "hello\n";
+        fun greet(name) {
+          if (name) {
+            print "${greeting}, " + name; // Hi.
+          }
+          return nil;
+        }
+        class A { m() { print 1 }
+    "#};
+
+    /// Apply `edit` to the source of `old`, check reparsing gives the tree
+    /// and errors of the result, and return the new tree.
+    fn reparsed(old: &Parse, edit: Edit) -> Parse {
+        let mut source = old.to_string();
+        source.replace_range(edit.range.clone(), &edit.text);
+        let reparsed = old.reparse(&source, &edit);
+        let expected = parse(&source);
+        assert_eq!(
+            format!("{:?}", reparsed.syntax()),
+            format!("{:?}", expected.syntax()),
+            "{edit:?}"
+        );
+        assert_eq!(reparsed.errors(), expected.errors(), "{edit:?}");
+        reparsed
+    }
+
+    #[test]
+    fn reuses_unchanged_nodes() {
+        let old = parse(SOURCE);
+        let statements = |parse: &Parse| -> Vec<_> { parse.syntax().children().collect() };
+
+        // Renaming the parameter only replaces the identifier.
+        let offset = SOURCE.find("name)").unwrap();
+        let new = reparsed(&old, Edit::new(offset..offset + 4, "who"));
+        assert!(Rc::ptr_eq(
+            statements(&new)[0].green(),
+            statements(&old)[0].green()
+        ));
+
+        // Editing the function body only reparses the if's block.
+        let offset = SOURCE.find("print \"").unwrap();
+        let new = reparsed(&old, Edit::new(offset..offset, "greet(1);\n    "));
+        assert!(Rc::ptr_eq(
+            statements(&new)[2].green(),
+            statements(&old)[2].green()
+        ));
+        let old_return = old
+            .syntax()
+            .descendants()
+            .find(|node| node.kind() == SyntaxKind::ReturnStmt);
+        let new_return = new
+            .syntax()
+            .descendants()
+            .find(|node| node.kind() == SyntaxKind::ReturnStmt);
+        assert!(Rc::ptr_eq(
+            new_return.unwrap().green(),
+            old_return.unwrap().green()
+        ));
+        assert_eq!(new.to_string().len(), SOURCE.len() + 14);
+    }
+
+    #[test]
+    fn matches_parsing_from_scratch() {
+        let old = parse(SOURCE);
+        let insertions = ["x", "\"", "\\", "/*", "${", "{", "}", ";", "(", "\n", " "];
+        for (i, c) in SOURCE.char_indices() {
+            reparsed(&old, Edit::new(i..i + c.len_utf8(), ""));
+            for insertion in insertions {
+                reparsed(&old, Edit::new(i..i, insertion));
+            }
+        }
+    }
+
+    #[test]
+    fn nesting_limit_counts_outer_nodes() {
+        let source = format!("{}print 1;{}", "{".repeat(1000), "}".repeat(1000));
+        let old = parse(&source);
+        assert!(old.errors().is_empty());
+        let offset = source.find("print").unwrap();
+        let deeper = format!("{}{}", "{".repeat(50), "}".repeat(50));
+        let new = reparsed(&old, Edit::new(offset..offset, deeper));
+        assert_eq!(new.errors().len(), 1);
+    }
+}
@@ -1,6 +1,10 @@
+use std::borrow::Cow;
 use std::{cmp::min, fmt};
 
-use crate::source::Span;
+use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+
+use crate::error::Error;
+use crate::source::{FileId, Span};
 
 #[derive(Clone, PartialEq)]
 pub struct Token {
@@ -36,12 +40,27 @@ impl Token {
         let view_len = min(len, 7);
         view[..view_len].copy_from_slice(&lexeme.as_bytes()[..view_len]);
         let span = Span {
+            file: FileId::default(),
             start: pos as u32,
             end: (pos + len) as u32,
         };
         Self { kind, span, view }
     }
 
+    /// Returns the token, moved to `file`.
+    pub fn in_file(mut self, file: FileId) -> Self {
+        self.span.file = file;
+        self
+    }
+
+    /// Returns the token, moved `delta` bytes along its file, like by an
+    /// edit before it.
+    pub fn shifted(mut self, delta: isize) -> Self {
+        self.span.start = (self.span.start as isize + delta) as u32;
+        self.span.end = (self.span.end as isize + delta) as u32;
+        self
+    }
+
     pub fn kind(&self) -> TokenKind {
         self.kind
     }
@@ -51,11 +70,14 @@ impl Token {
     }
 
     /// Reads the view and determines if it's a partial or complete view.
-    pub fn view(&self) -> LexView {
+    pub fn view(&self) -> LexView<'_> {
         let len = (self.span.end - self.span.start) as usize;
         let view_len = min(len, 7);
-        // SAFETY: view_len is validated to be <= 7
-        let view = unsafe { str::from_utf8_unchecked(&self.view[..view_len]) };
+        // The view may end partway through a multibyte char, so drop it.
+        let view = &self.view[..view_len];
+        let view = str::from_utf8(view).unwrap_or_else(|e| {
+            str::from_utf8(&view[..e.valid_up_to()]).expect("valid UTF-8 prefix")
+        });
         if len <= 7 {
             LexView::Complete(view)
         } else {
@@ -73,6 +95,39 @@ impl Token {
         }
     }
 
+    /// Returns the name of an identifier token in NFC, so names that look the
+    /// same compare equal however they were encoded.
+    ///
+    /// Panics if this token is not from the provided source.
+    pub fn identifier<'a>(&'a self, source: &'a str) -> Cow<'a, str> {
+        let lexeme = self.lexeme(source);
+        if lexeme.is_ascii() || is_nfc_quick(lexeme.chars()) == IsNormalized::Yes {
+            Cow::Borrowed(lexeme)
+        } else {
+            Cow::Owned(lexeme.nfc().collect())
+        }
+    }
+
+    /// Returns the value of a string literal token, or a segment of an
+    /// interpolated string: see [`decode_string`].
+    ///
+    /// Panics if this token is not from the provided source.
+    pub fn string_value(&self, source: &str) -> Result<String, Error> {
+        decode_string(self.kind, self.lexeme(source), &self.span)
+    }
+
+    /// Returns the value of a number literal token.
+    ///
+    /// Panics if this token is not a valid number from the provided source.
+    pub fn number_value(&self, source: &str) -> f64 {
+        // The scanner only produces valid number lexemes.
+        parse_number(self.lexeme(source)).expect("invalid number literal")
+    }
+
+    pub fn new_whitespace(pos: usize, lexeme: &str) -> Self {
+        Token::new(TokenKind::Whitespace, pos, lexeme)
+    }
+
     pub fn new_line_comment(pos: usize, lexeme: &str) -> Self {
         Token::new(TokenKind::LineComment, pos, lexeme)
     }
@@ -165,13 +220,24 @@ impl Token {
         Token::new(TokenKind::String, pos, lexeme)
     }
 
+    pub fn new_string_head(pos: usize, lexeme: &str) -> Self {
+        Token::new(TokenKind::StringHead, pos, lexeme)
+    }
+
+    pub fn new_string_middle(pos: usize, lexeme: &str) -> Self {
+        Token::new(TokenKind::StringMiddle, pos, lexeme)
+    }
+
+    pub fn new_string_tail(pos: usize, lexeme: &str) -> Self {
+        Token::new(TokenKind::StringTail, pos, lexeme)
+    }
+
     pub fn new_number(pos: usize, lexeme: &str) -> Self {
         Token::new(TokenKind::Number, pos, lexeme)
     }
 
-    /// Panics if `lexeme` is not a valid keyword.
-    pub fn new_keyword(pos: usize, lexeme: &str) -> Self {
-        Token::new(TokenKind::Keyword(Keyword::from(lexeme)), pos, lexeme)
+    pub fn new_keyword(pos: usize, keyword: Keyword) -> Self {
+        Token::new(TokenKind::Keyword(keyword), pos, keyword.as_str())
     }
 
     pub fn new_unterminated_block_comment(pos: usize, lexeme: &str) -> Self {
@@ -182,62 +248,250 @@ impl Token {
         Token::new(TokenKind::UnterminatedString, pos, lexeme)
     }
 
+    pub fn new_invalid_number(pos: usize, lexeme: &str) -> Self {
+        Token::new(TokenKind::InvalidNumber, pos, lexeme)
+    }
+
     pub fn new_invalid_character(pos: usize, lexeme: &str) -> Self {
         Token::new(TokenKind::InvalidCharacter, pos, lexeme)
     }
 
+    /// Return true if the token is trivia the parser skips, like a comment.
+    pub fn is_trivia(&self) -> bool {
+        self.kind.is_trivia()
+    }
+
     /// Return true if the token is invalid.
     pub fn is_invalid(&self) -> bool {
-        matches!(self.kind, TokenKind::InvalidCharacter)
+        matches!(
+            self.kind,
+            TokenKind::InvalidNumber | TokenKind::InvalidCharacter
+        )
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Keyword {
-    And,
-    Class,
-    Else,
-    False,
-    Fun,
-    For,
-    If,
-    Nil,
-    Or,
-    Print,
-    Return,
-    Super,
-    This,
-    True,
-    Var,
-    While,
+/// Decode a string literal, or a segment of an interpolated string, of
+/// `kind` at `span`, with its delimiters stripped and escape sequences
+/// decoded. Supports `\n`, `\t`, `\\`, `\"`, `\0`
+/// and `\u{XXXX}` with 1 to 6 hex digits.
+pub fn decode_string(kind: TokenKind, lexeme: &str, span: &Span) -> Result<String, Error> {
+    // Every segment opens with a one byte `"` or `}`, and closes with a
+    // `"` or a `${`.
+    let close = match kind {
+        TokenKind::StringHead | TokenKind::StringMiddle => 2,
+        _ => 1,
+    };
+    let body = &lexeme[1..lexeme.len() - close];
+    // Offset of `body` in the source.
+    let offset = span.start as usize + 1;
+    let invalid = |start: usize, end: usize| Error::InvalidEscape {
+        span: Span {
+            file: span.file,
+            start: (offset + start) as u32,
+            end: (offset + end) as u32,
+        },
+    };
+
+    let mut value = String::with_capacity(body.len());
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        let decoded = match chars.next() {
+            Some((_, 'n')) => '\n',
+            Some((_, 't')) => '\t',
+            Some((_, '\\')) => '\\',
+            Some((_, '"')) => '"',
+            Some((_, '0')) => '\0',
+            Some((_, 'u')) => {
+                let rest = &body[i + 2..];
+                let Some(digits) = rest
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.find('}').map(|end| &rest[..end]))
+                else {
+                    return Err(invalid(i, i + 2));
+                };
+                // Cover the braces too.
+                let end = i + 2 + digits.len() + 2;
+                let c = Some(digits)
+                    .filter(|d| (1..=6).contains(&d.len()))
+                    .filter(|d| d.chars().all(|c| c.is_ascii_hexdigit()))
+                    .and_then(|d| u32::from_str_radix(d, 16).ok())
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| invalid(i, end))?;
+                // The digits and braces are all ASCII, so one byte each.
+                chars.nth(digits.len() + 1);
+                c
+            }
+            Some((j, c)) => return Err(invalid(i, j + c.len_utf8())),
+            None => return Err(invalid(i, i + 1)),
+        };
+        value.push(decoded);
+    }
+    Ok(value)
+}
+
+/// Parse a number literal, or return `None` if it's malformed.
+///
+/// Literals are decimal, like `12`, `1.5` or `6.02e-23`, or integers with a
+/// radix prefix, like `0xFF`, `0b1010` or `0o17`. Digits may be separated by
+/// single underscores, like `1_000`.
+pub fn parse_number(lexeme: &str) -> Option<f64> {
+    let radix = match lexeme.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0b" | "0B") => 2,
+        Some("0o" | "0O") => 8,
+        _ => 10,
+    };
+    if radix != 10 {
+        let digits = &lexeme[2..];
+        if !valid_digits(digits, radix) {
+            return None;
+        }
+        // Accumulate as a float, so large literals round instead of
+        // overflowing.
+        let value = digits
+            .chars()
+            .filter_map(|c| c.to_digit(radix))
+            .fold(0.0, |value, digit| {
+                value * f64::from(radix) + f64::from(digit)
+            });
+        return Some(value);
+    }
+
+    let (mantissa, exponent) = match lexeme.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (lexeme, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let valid = valid_digits(integer, 10)
+        && fraction.is_none_or(|fraction| valid_digits(fraction, 10))
+        && exponent.is_none_or(|exponent| {
+            let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+            valid_digits(exponent, 10)
+        });
+    if !valid {
+        return None;
+    }
+    lexeme.replace('_', "").parse().ok()
+}
+
+/// Return true if `digits` is a non-empty run of digits in `radix`, with
+/// single underscores allowed between them.
+fn valid_digits(digits: &str, radix: u32) -> bool {
+    !digits.is_empty()
+        && digits
+            .split('_')
+            .all(|group| !group.is_empty() && group.chars().all(|c| c.is_digit(radix)))
 }
 
-impl From<&str> for Keyword {
-    fn from(value: &str) -> Self {
-        match value {
-            "and" => Self::And,
-            "class" => Self::Class,
-            "else" => Self::Else,
-            "false" => Self::False,
-            "fun" => Self::Fun,
-            "for" => Self::For,
-            "if" => Self::If,
-            "nil" => Self::Nil,
-            "or" => Self::Or,
-            "print" => Self::Print,
-            "return" => Self::Return,
-            "super" => Self::Super,
-            "this" => Self::This,
-            "true" => Self::True,
-            "var" => Self::Var,
-            "while" => Self::While,
-            _ => unimplemented!("invalid keyword {value}"),
+/// Define [`Keyword`] from a table of variants and their lexemes, so adding a
+/// keyword is a one line change.
+macro_rules! keywords {
+    ($($variant:ident => $lexeme:literal,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub enum Keyword {
+            $(
+                #[doc = concat!("`", $lexeme, "`")]
+                $variant,
+            )*
         }
+
+        impl Keyword {
+            /// Every keyword, in alphabetical order.
+            pub const ALL: &[Keyword] = &[$(Keyword::$variant,)*];
+
+            /// Returns the keyword spelled `lexeme`, or `None` if it isn't
+            /// reserved. Every identifier is looked up, so this hashes it to
+            /// the one keyword it could be, and compares it with just that.
+            // Not `FromStr`: an identifier that isn't a keyword isn't an error.
+            #[allow(clippy::should_implement_trait)]
+            pub fn from_str(lexeme: &str) -> Option<Self> {
+                let keyword = KEYWORD_TABLE[keyword_hash(lexeme.as_bytes())?]?;
+                (keyword.as_str() == lexeme).then_some(keyword)
+            }
+
+            pub const fn as_str(self) -> &'static str {
+                match self {
+                    $(Keyword::$variant => $lexeme,)*
+                }
+            }
+        }
+    };
+}
+
+/// The size of [`KEYWORD_TABLE`].
+const KEYWORD_TABLE_SIZE: usize = 32;
+
+/// Keywords by [`keyword_hash`] of their lexeme.
+static KEYWORD_TABLE: [Option<Keyword>; KEYWORD_TABLE_SIZE] = {
+    let mut table = [None; KEYWORD_TABLE_SIZE];
+    let mut i = 0;
+    while i < Keyword::ALL.len() {
+        let keyword = Keyword::ALL[i];
+        let Some(hash) = keyword_hash(keyword.as_str().as_bytes()) else {
+            unreachable!();
+        };
+        // Fails the build if a new keyword collides, so change the hash.
+        assert!(table[hash].is_none(), "keywords with the same hash");
+        table[hash] = Some(keyword);
+        i += 1;
+    }
+    table
+};
+
+/// A perfect hash of the keywords, from their first and last bytes and
+/// length, or `None` for an empty lexeme.
+const fn keyword_hash(lexeme: &[u8]) -> Option<usize> {
+    let (Some(&first), Some(&last)) = (lexeme.first(), lexeme.last()) else {
+        return None;
+    };
+    Some((first as usize + lexeme.len() + 5 * last as usize) % KEYWORD_TABLE_SIZE)
+}
+
+keywords! {
+    And => "and",
+    Class => "class",
+    Else => "else",
+    False => "false",
+    Fun => "fun",
+    For => "for",
+    If => "if",
+    Nil => "nil",
+    Or => "or",
+    Print => "print",
+    Return => "return",
+    Super => "super",
+    This => "this",
+    True => "true",
+    Var => "var",
+    While => "while",
+}
+
+impl Keyword {
+    /// Returns an iterator over every keyword, in alphabetical order.
+    pub fn iter() -> impl Iterator<Item = Keyword> {
+        Keyword::ALL.iter().copied()
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenKind {
+    /// A run of whitespace, only kept by a lossless [`Scanner`].
+    ///
+    /// [`Scanner`]: crate::scanner::Scanner
+    Whitespace,
     /// Line `// comment`.
     LineComment,
     /// Block `/* comment */`.
@@ -287,7 +541,14 @@ pub enum TokenKind {
     Identifier,
     /// A raw UTF-8 string literal in double quotes, like `"Hello, world!"`.
     String,
-    /// A number literal, like `123` or `1.5`.
+    /// The start of an interpolated string, up to its first hole, like
+    /// `"Hello, ${`.
+    StringHead,
+    /// The part of an interpolated string between two holes, like `} and ${`.
+    StringMiddle,
+    /// The end of an interpolated string, after its last hole, like `}!"`.
+    StringTail,
+    /// A number literal, like `123`, `1_000.5`, `6.02e23` or `0xFF`.
     Number,
     /// A reserved keyword, like `class`.
     Keyword(Keyword),
@@ -296,8 +557,41 @@ pub enum TokenKind {
     UnterminatedBlockComment,
     /// An unterminated string.
     UnterminatedString,
+    /// A malformed number, like `0x` or `1e`.
+    InvalidNumber,
     /// An invalid character.
     InvalidCharacter,
+    /// The end of the input. Never scanned, but ends every syntax tree to hold
+    /// the trivia after the last token.
+    Eof,
+}
+
+impl TokenKind {
+    /// Return true for trivia the parser skips, like whitespace or comments.
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment
+        )
+    }
+
+    /// Return true for the keywords a declaration or statement can start
+    /// with, where parsing resumes after an error.
+    pub fn starts_declaration(self) -> bool {
+        matches!(
+            self,
+            TokenKind::Keyword(
+                Keyword::Class
+                    | Keyword::Fun
+                    | Keyword::Var
+                    | Keyword::For
+                    | Keyword::If
+                    | Keyword::While
+                    | Keyword::Print
+                    | Keyword::Return
+            )
+        )
+    }
 }
 
 impl fmt::Display for Token {
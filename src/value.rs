@@ -0,0 +1,62 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::class::{Class, Instance};
+use crate::function::{Function, NativeFunction};
+
+/// A runtime Lox value.
+///
+/// Values may borrow from the source and syntax tree they were created from,
+/// e.g., a function refers to its declaration.
+#[derive(Debug, Clone)]
+pub enum Value<'src> {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Function(Rc<Function<'src>>),
+    NativeFunction(NativeFunction),
+    Class(Rc<Class<'src>>),
+    Instance(Rc<RefCell<Instance<'src>>>),
+}
+
+impl Value<'_> {
+    /// Lox follows Ruby's rule: `false` and `nil` are falsey, everything else
+    /// is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+}
+
+/// Functions, classes and instances are equal only to themselves.
+impl PartialEq for Value<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(b), Value::Bool(other_b)) => b == other_b,
+            (Value::Number(n), Value::Number(other_n)) => n == other_n,
+            (Value::String(s), Value::String(other_s)) => s == other_s,
+            (Value::Function(f), Value::Function(other_f)) => Rc::ptr_eq(f, other_f),
+            (Value::NativeFunction(f), Value::NativeFunction(other_f)) => f.name == other_f.name,
+            (Value::Class(c), Value::Class(other_c)) => Rc::ptr_eq(c, other_c),
+            (Value::Instance(i), Value::Instance(other_i)) => Rc::ptr_eq(i, other_i),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => f.write_str("nil"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Number(n) => write!(f, "{n}"),
+            Value::String(s) => f.write_str(s),
+            Value::Function(function) => write!(f, "<fn {}>", function.name()),
+            Value::NativeFunction(_) => f.write_str("<native fn>"),
+            Value::Class(class) => f.write_str(&class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+        }
+    }
+}